@@ -0,0 +1,232 @@
+//! Applying a reviewed subset of the findings of `-o json`
+//!
+//! Each file is analyzed again, and only the [`DeadCode`] that matches
+//! a remaining finding by rule, binding name, scope kind and location
+//! is removed with [`edit_dead_code()`]. Findings that match nothing
+//! are stale because their file has changed since, and are refused.
+//!
+//! Findings of `--follow-imports` only match if the imported files are
+//! analyzed again, too. The [`Imports`] that are passed to
+//! [`Findings::apply()`] should therefore be filled with
+//! [`Imports::add_imported()`] for all files of the findings, before
+//! any of them is edited.
+
+use crate::{dead_code::DeadCode, edit::edit_dead_code, Imports, Settings, JSON_SCHEMA_VERSION};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fmt, path::Path};
+
+/// The remaining findings of one file
+#[derive(Debug, Clone)]
+pub struct Findings {
+    /// Path of the file as printed by `-o json`
+    pub file: String,
+    /// Findings in the order of the report
+    pub entries: Vec<Finding>,
+}
+
+/// A finding that identifies a [`DeadCode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// [`DeadCode::rule_id()`]
+    pub rule_id: String,
+    /// [`Scope::kind()`](crate::Scope::kind)
+    pub scope: String,
+    /// Name of the binding
+    pub name: String,
+    /// Byte offset of the start of the name
+    pub start: usize,
+    /// Byte offset of the end of the name
+    pub end: usize,
+}
+
+impl Finding {
+    fn matches(&self, dead_code: &DeadCode) -> bool {
+        let range = dead_code.binding.name.text_range();
+        self.rule_id == dead_code.rule_id()
+            && self.scope == dead_code.scope.kind()
+            && self.name == dead_code.binding.name.as_str()
+            && self.start == usize::from(range.start())
+            && self.end == usize::from(range.end())
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{} at {}..{} [{}]",
+            self.name, self.start, self.end, self.rule_id
+        )
+    }
+}
+
+/// Error reading a findings file
+#[derive(Debug)]
+pub enum Error {
+    /// Not the JSON of `-o json`
+    Json(serde_json::Error),
+    /// Written by an incompatible version of deadnix
+    SchemaVersion(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(error) => write!(fmt, "Invalid findings: {error}"),
+            Error::SchemaVersion(version) => write!(
+                fmt,
+                "Unsupported findings schema version {version}, expected {JSON_SCHEMA_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    schema_version: u32,
+    file: String,
+    results: Vec<Entry>,
+}
+
+/// A result of `-o json`, which is only a `message` for files that
+/// could not be read or parsed
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    rule_id: Option<String>,
+    scope: Option<String>,
+    binding: Option<EntryBinding>,
+}
+
+#[derive(Deserialize)]
+struct EntryBinding {
+    name: String,
+    range: EntryRange,
+}
+
+#[derive(Deserialize)]
+struct EntryRange {
+    start: EntryPosition,
+    end: EntryPosition,
+}
+
+#[derive(Deserialize)]
+struct EntryPosition {
+    offset: usize,
+}
+
+/// Read the output of `-o json`, a sequence of one object per file
+/// that may also be wrapped in an array
+///
+/// The findings of a file that occurs more than once are merged.
+///
+/// # Errors
+///
+/// Fails if `json` is not in the structure of
+/// [`JSON_SCHEMA_VERSION`].
+pub fn read_findings(json: &str) -> Result<Vec<Findings>, Error> {
+    let mut reports = Vec::new();
+    for value in serde_json::Deserializer::from_str(json).into_iter::<Value>() {
+        match value? {
+            Value::Array(values) => {
+                for value in values {
+                    reports.push(Report::deserialize(value)?);
+                }
+            }
+            value => reports.push(Report::deserialize(value)?),
+        }
+    }
+
+    let mut results: Vec<Findings> = Vec::new();
+    for report in reports {
+        if report.schema_version != JSON_SCHEMA_VERSION {
+            return Err(Error::SchemaVersion(report.schema_version));
+        }
+        let entries = report.results.into_iter().filter_map(|entry| {
+            let binding = entry.binding?;
+            Some(Finding {
+                rule_id: entry.rule_id?,
+                scope: entry.scope?,
+                name: binding.name,
+                start: binding.range.start.offset,
+                end: binding.range.end.offset,
+            })
+        });
+        if let Some(findings) = results
+            .iter_mut()
+            .find(|findings| findings.file == report.file)
+        {
+            findings.entries.extend(entries);
+        } else {
+            results.push(Findings {
+                file: report.file,
+                entries: entries.collect(),
+            });
+        }
+    }
+    Ok(results)
+}
+
+impl Findings {
+    /// Remove the dead code of the findings from the current `content`
+    /// of their file
+    ///
+    /// Returns the edited content, whether it has changed, and the
+    /// stale findings that match no dead code in `content` and have
+    /// therefore not been applied.
+    ///
+    /// `import ./file.nix args` is resolved with `imports`. Resolving
+    /// it only reports more pattern names, so that findings with and
+    /// without `--follow-imports` are matched alike.
+    pub fn apply(&self, content: &str, imports: &Imports) -> (String, bool, Vec<&Finding>) {
+        let ast = rnix::Root::parse(content);
+        if !ast.errors().is_empty() {
+            // the findings were reported for a file without errors
+            return (content.to_string(), false, self.entries.iter().collect());
+        }
+
+        let results =
+            all_checks().find_dead_code_with_imports(&ast.syntax(), Path::new(&self.file), imports);
+        let stale = self
+            .entries
+            .iter()
+            .filter(|entry| !results.iter().any(|result| entry.matches(result)))
+            .collect();
+        let (edited, has_changes) = edit_dead_code(
+            content,
+            results
+                .into_iter()
+                .filter(|result| self.entries.iter().any(|entry| entry.matches(result))),
+        );
+        (edited, has_changes, stale)
+    }
+}
+
+/// Settings that report everything that the findings may have been
+/// reported with
+///
+/// Disabled checks only keep more bindings alive, so that the results
+/// include those of any other settings.
+fn all_checks() -> Settings {
+    Settings {
+        no_lambda_arg: false,
+        no_lambda_pattern_names: false,
+        no_top_lambda_pattern_names: false,
+        no_underscore: false,
+        warn_used_underscore: true,
+        warn_unused_with: true,
+        warn_unused_rec: true,
+        warn_unused_attrs: true,
+        warn_shadowing: true,
+    }
+}
@@ -0,0 +1,176 @@
+#![cfg(all(test, feature = "json-out"))]
+
+use crate::{
+    apply::{read_findings, Error, Findings},
+    report::json_report,
+    Imports, Settings,
+};
+
+const CONTENT: &str = "let\n  a = 1;\n  b = 2;\nin x: 3\n";
+
+/// The `-o json` output for `content`
+fn report(settings: &Settings, content: &str) -> String {
+    let ast = rnix::Root::parse(content);
+    let results = settings.find_dead_code(&ast.syntax());
+    json_report("test.nix", content, &results).to_string()
+}
+
+/// `report` with only the findings of `names`
+fn reviewed(report: &str, names: &[&str]) -> Vec<Findings> {
+    let mut findings = read_findings(report).unwrap();
+    for findings in &mut findings {
+        findings
+            .entries
+            .retain(|entry| names.contains(&entry.name.as_str()));
+    }
+    findings
+}
+
+#[test]
+fn apply_all() {
+    let findings = reviewed(&report(&Settings::default(), CONTENT), &["a", "b", "x"]);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].file, "test.nix");
+    let (edited, has_changes, stale) = findings[0].apply(CONTENT, &Imports::new());
+    assert!(has_changes);
+    assert!(stale.is_empty());
+    let ast = rnix::Root::parse(CONTENT);
+    let expected = crate::edit_dead_code(
+        CONTENT,
+        Settings::default()
+            .find_dead_code(&ast.syntax())
+            .into_iter(),
+    );
+    assert_eq!((edited.as_str(), true), (expected.0.as_str(), expected.1));
+    assert_eq!(edited, "_x: 3\n");
+}
+
+#[test]
+fn apply_subset() {
+    let findings = reviewed(&report(&Settings::default(), CONTENT), &["b"]);
+    let (edited, has_changes, stale) = findings[0].apply(CONTENT, &Imports::new());
+    assert!(has_changes);
+    assert!(stale.is_empty());
+    assert_eq!(edited, "let\n  a = 1;\nin x: 3\n");
+
+    let findings = reviewed(&report(&Settings::default(), CONTENT), &[]);
+    let (edited, has_changes, _) = findings[0].apply(CONTENT, &Imports::new());
+    assert!(!has_changes);
+    assert_eq!(edited, CONTENT);
+}
+
+#[test]
+fn apply_stale() {
+    let findings = reviewed(&report(&Settings::default(), CONTENT), &["a", "b"]);
+
+    // `b` has moved
+    let changed = CONTENT.replace("a = 1;", "a = 10;");
+    let (edited, has_changes, stale) = findings[0].apply(&changed, &Imports::new());
+    assert!(has_changes);
+    assert_eq!(
+        stale
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>(),
+        ["b"]
+    );
+    assert_eq!(edited, "let\n  b = 2;\nin x: 3\n");
+
+    // `a` is used now
+    let changed = CONTENT.replace("x: 3", "x: a");
+    let (_, _, stale) = findings[0].apply(&changed, &Imports::new());
+    assert_eq!(
+        stale
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>(),
+        ["a"]
+    );
+
+    // syntax errors
+    let (edited, has_changes, stale) = findings[0].apply("let a = 1;", &Imports::new());
+    assert!(!has_changes);
+    assert_eq!(stale.len(), 2);
+    assert_eq!(edited, "let a = 1;");
+}
+
+#[test]
+fn apply_with_settings() {
+    // findings of non-default settings are matched, too
+    let settings = Settings {
+        warn_used_underscore: true,
+        warn_unused_rec: true,
+        ..Settings::default()
+    };
+    let content = "let _a = 1; in rec { b = _a; }";
+    let findings = reviewed(&report(&settings, content), &["_a", "rec"]);
+    assert_eq!(findings[0].entries.len(), 2);
+    let (edited, has_changes, stale) = findings[0].apply(content, &Imports::new());
+    assert!(has_changes);
+    assert!(stale.is_empty());
+    assert_eq!(edited, "let _a = 1; in { b = _a; }");
+}
+
+#[test]
+fn apply_follow_imports() {
+    let caller = "{ alive, dead, ... }@args: import ./impl.nix args";
+    let imported = "{ alive, ... }: alive";
+    let mut imports = Imports::new();
+    imports.add(
+        std::path::Path::new("/nonexistent/impl.nix"),
+        &rnix::Root::parse(imported).syntax(),
+    );
+    let ast = rnix::Root::parse(caller);
+    let results = Settings::default().find_dead_code_with_imports(
+        &ast.syntax(),
+        std::path::Path::new("/nonexistent/caller.nix"),
+        &imports,
+    );
+    let report = json_report("/nonexistent/caller.nix", caller, &results).to_string();
+    let findings = reviewed(&report, &["dead"]);
+    assert_eq!(findings[0].entries.len(), 1);
+
+    // without the imported file, `dead` is consumed through `args`
+    let (edited, has_changes, stale) = findings[0].apply(caller, &Imports::new());
+    assert!(!has_changes);
+    assert_eq!(stale.len(), 1);
+    assert_eq!(edited, caller);
+
+    let (edited, has_changes, stale) = findings[0].apply(caller, &imports);
+    assert!(has_changes);
+    assert!(stale.is_empty());
+    assert_eq!(edited, "{ alive, ... }@args: import ./impl.nix args");
+}
+
+#[test]
+fn read_findings_formats() {
+    let report = report(&Settings::default(), CONTENT);
+    let error = r#"{"schemaVersion": 1, "file": "error.nix", "results": [{"message": "unexpected end of file"}]}"#;
+
+    // one object per line, pretty printed, wrapped in an array
+    let pretty =
+        serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(&report).unwrap())
+            .unwrap();
+    for json in [
+        format!("{report}\n{error}\n"),
+        format!("{pretty}\n{error}"),
+        format!("[{report}, {error}]"),
+    ] {
+        let findings = read_findings(&json).unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].entries.len(), 3);
+        assert_eq!(findings[1].file, "error.nix");
+        assert!(findings[1].entries.is_empty());
+    }
+
+    // the same file twice
+    let findings = read_findings(&format!("{report}\n{report}")).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].entries.len(), 6);
+
+    assert!(matches!(read_findings("{"), Err(Error::Json(_))));
+    assert!(matches!(
+        read_findings(r#"{"schemaVersion": 2, "file": "test.nix", "results": []}"#),
+        Err(Error::SchemaVersion(2))
+    ));
+}
@@ -1,5 +1,12 @@
-use rnix::{ast::Ident, NixLanguage, SyntaxKind};
-use rowan::{api::SyntaxNode, ast::AstNode};
+use rnix::{
+    ast::{Attr, Ident, InterpolPart},
+    NixLanguage, SyntaxKind, TextRange,
+};
+use rowan::{
+    api::{SyntaxNode, SyntaxToken},
+    ast::AstNode,
+};
+use std::fmt;
 
 /// This string in a Nix comment above an unused declaration shall
 /// force us to skip it.
@@ -11,11 +18,91 @@ use rowan::{api::SyntaxNode, ast::AstNode};
 /// ```
 const PRAGMA_SKIP: &str = "deadnix: skip";
 
+/// Name of a [`Binding`]
+///
+/// Usually an identifier or a static string key like `"name"`, but a
+/// `with` expression is named by the syntax of its namespace, and a
+/// `rec { ... }` by its keyword.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name {
+    node: SyntaxNode<NixLanguage>,
+    range: TextRange,
+    text: String,
+}
+
+impl Name {
+    /// Create a new Name from the syntax `node` that spells it
+    pub fn new(node: SyntaxNode<NixLanguage>) -> Self {
+        let range = node.text_range();
+        let text = node.to_string();
+        Name { node, range, text }
+    }
+
+    /// Create a new Name from a keyword `token`
+    pub fn from_token(token: &SyntaxToken<NixLanguage>) -> Self {
+        Name {
+            node: token.parent().expect("token.parent"),
+            range: token.text_range(),
+            text: token.text().to_string(),
+        }
+    }
+
+    /// Create a new Name from an attribute key that is an identifier
+    /// or a string without any `${...}` interpolation
+    pub fn from_attr(attr: &Attr) -> Option<Self> {
+        match attr {
+            Attr::Ident(ident) => Some(ident.clone().into()),
+            Attr::Str(string) => {
+                let mut text = String::new();
+                for part in string.normalized_parts() {
+                    match part {
+                        InterpolPart::Literal(literal) => text.push_str(&literal),
+                        InterpolPart::Interpolation(_) => return None,
+                    }
+                }
+                Some(Name {
+                    node: string.syntax().clone(),
+                    range: string.syntax().text_range(),
+                    text,
+                })
+            }
+            Attr::Dynamic(_) => None,
+        }
+    }
+
+    /// Syntax node of the name, or the node containing its keyword
+    pub fn syntax(&self) -> &SyntaxNode<NixLanguage> {
+        &self.node
+    }
+
+    /// Location of the name in the source
+    pub fn text_range(&self) -> TextRange {
+        self.range
+    }
+
+    /// The name as a string
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+impl From<Ident> for Name {
+    fn from(ident: Ident) -> Self {
+        Name::new(ident.syntax().clone())
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(&self.text)
+    }
+}
+
 /// A Nix variable binding
 #[derive(Debug, Clone)]
 pub struct Binding {
     /// Variable name
-    pub name: Ident,
+    pub name: Name,
     /// Syntax node of declaration itself
     pub decl_node: SyntaxNode<NixLanguage>,
     mortal: bool,
@@ -23,9 +110,9 @@ pub struct Binding {
 
 impl Binding {
     /// Create a new Binding
-    pub fn new(name: Ident, decl_node: SyntaxNode<NixLanguage>, mortal: bool) -> Self {
+    pub fn new(name: impl Into<Name>, decl_node: SyntaxNode<NixLanguage>, mortal: bool) -> Self {
         Binding {
-            name,
+            name: name.into(),
             decl_node,
             mortal,
         }
@@ -42,7 +129,7 @@ impl Binding {
     /// Does the name start with `_`, signifying an anonymous
     /// variable?
     pub fn starts_with_underscore(&self) -> bool {
-        self.name.syntax().text().char_at(0.into()) == Some('_')
+        self.name.as_str().starts_with('_')
     }
 
     /// Searches through tokens backwards for `PRAGMA_SKIP` until at
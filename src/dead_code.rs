@@ -1,9 +1,19 @@
-use crate::{binding::Binding, scope::Scope, usage};
-use rnix::{ast::Inherit, NixLanguage, SyntaxKind};
+use crate::{
+    binding::{Binding, Name},
+    imports::Imports,
+    resolve::{Index, Reference},
+    scope::Scope,
+    session::ScopeCache,
+};
+use rnix::{
+    ast::{Attr, AttrSet, AttrpathValue, Expr, HasAttr, Inherit, Select, With},
+    NixLanguage, SyntaxKind,
+};
 use rowan::{api::SyntaxNode, ast::AstNode};
 use std::{
     collections::{HashMap, HashSet},
     fmt,
+    path::Path,
 };
 
 /// Instance of a dead binding
@@ -15,13 +25,21 @@ pub struct DeadCode {
     pub scope: Scope,
     /// The [`Binding`] that is found to be unused
     pub binding: Binding,
+    /// The [`Binding`] of an enclosing [`Scope`] that is shadowed by
+    /// [`binding`](`DeadCode::binding`), see
+    /// [`Settings::warn_shadowing`]
+    pub shadowed: Option<(Scope, Binding)>,
     /// Used or unused?
-    unused: bool,
+    pub(crate) unused: bool,
 }
 
 impl fmt::Display for DeadCode {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        if self.unused {
+        if self.shadowed.is_some() {
+            write!(fmt, "Shadowing {}: {}", self.scope, self.binding.name)
+        } else if let Scope::RecAttrSet(_) = self.scope {
+            write!(fmt, "Superfluous {}", self.scope)
+        } else if self.unused {
             write!(fmt, "Unused {}: {}", self.scope, self.binding.name)
         } else {
             write!(fmt, "Used {}: {}", self.scope, self.binding.name)
@@ -29,36 +47,117 @@ impl fmt::Display for DeadCode {
     }
 }
 
+impl DeadCode {
+    /// `unused`, `used-underscore` or `shadowing`
+    pub(crate) fn status(&self) -> &'static str {
+        if self.shadowed.is_some() {
+            "shadowing"
+        } else if self.unused {
+            "unused"
+        } else {
+            "used-underscore"
+        }
+    }
+
+    /// Identifier of the kind of finding, like `unused-let-binding`
+    pub fn rule_id(&self) -> &'static str {
+        if self.shadowed.is_some() {
+            return "shadowing";
+        }
+        if !self.unused {
+            return "used-underscore";
+        }
+        match self.scope {
+            Scope::LambdaPattern(_, _) => "unused-lambda-pattern",
+            Scope::LambdaArg(_, _) => "unused-lambda-arg",
+            Scope::LetIn(_) => "unused-let-binding",
+            Scope::LegacyLet(_) => "unused-legacy-let-binding",
+            Scope::RecAttrSet(_) => "superfluous-rec",
+            Scope::LetAttrSet(_) => "unused-let-attribute",
+            Scope::With(_) => "unused-with",
+        }
+    }
+}
+
 /// Analysis settings
+///
+/// Deserializes from camelCase fields, see [`lsp`](crate::lsp).
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "json-out",
+    derive(serde::Deserialize),
+    serde(default, rename_all = "camelCase")
+)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Settings {
     /// Ignore `...: ...`
     pub no_lambda_arg: bool,
     /// Ignore `{ ... }: ...`
     pub no_lambda_pattern_names: bool,
+    /// Ignore `{ ... }: ...` only for the lambda of the whole file,
+    /// like the `callPackage` convention of nixpkgs
+    pub no_top_lambda_pattern_names: bool,
     /// Ignore all `Binding` that start with `_`
     pub no_underscore: bool,
     /// Warn on used binding that starts with `_`
     pub warn_used_underscore: bool,
+    /// Warn on `with ...; ...` whose namespace cannot provide any
+    /// variable
+    pub warn_unused_with: bool,
+    /// Warn on `rec { ... }` that does not reference its own bindings
+    pub warn_unused_rec: bool,
+    /// Warn on attributes of `let name = { ... };` that are never
+    /// selected, unless `name` is used other than by selection
+    pub warn_unused_attrs: bool,
+    /// Warn on bindings that shadow a binding of an enclosing scope
+    pub warn_shadowing: bool,
 }
 
 impl Settings {
     /// Find unused bindings
     ///
-    /// Loops until no more new [`Binding`] is found that is used only
-    /// by [`DeadCode`] that was found in a previous iteration.
+    /// Resolves all variable occurrences in one pass. A [`Binding`]
+    /// is alive if it is reachable from the bodies of its [`Scope`]
+    /// that do not belong to any mortal binding, such as the `in`
+    /// body of a `let`. Unreachable bindings are dead, including
+    /// groups of bindings that only reference each other.
     pub fn find_dead_code(&self, node: &SyntaxNode<NixLanguage>) -> Vec<DeadCode> {
-        let mut dead = HashSet::new();
-        let mut results = HashMap::new();
-        let mut prev_results_len = 1;
-        while prev_results_len != results.len() {
-            prev_results_len = results.len();
-            self.scan(node, &mut dead, &mut results);
-        }
+        self.find_dead_code_in(node, &Index::new(node))
+    }
 
-        let mut results = results.into_values().collect::<Vec<_>>();
-        results.sort_unstable_by_key(|result| result.binding.name.syntax().text_range().start());
+    /// Find unused bindings of the file at `path`, looking into the
+    /// files of `import ./file.nix args`
+    ///
+    /// The pattern names of `{ ... }@args:` are only considered used
+    /// through `args` if the imported file is part of `imports` and
+    /// its top-level lambda uses them, too.
+    pub fn find_dead_code_with_imports(
+        &self,
+        node: &SyntaxNode<NixLanguage>,
+        path: &Path,
+        imports: &Imports,
+    ) -> Vec<DeadCode> {
+        let mut index = Index::new(node);
+        index.resolve_imports(path.parent().unwrap_or(Path::new(".")), imports);
+        self.find_dead_code_in(node, &index)
+    }
+
+    fn find_dead_code_in(&self, node: &SyntaxNode<NixLanguage>, index: &Index) -> Vec<DeadCode> {
+        self.find_dead_code_cached(node, index, None)
+    }
+
+    /// Find unused bindings, reusing the reachability of scopes that
+    /// are unchanged since the analysis that filled `cache`
+    pub(crate) fn find_dead_code_cached(
+        &self,
+        node: &SyntaxNode<NixLanguage>,
+        index: &Index,
+        cache: Option<&mut ScopeCache>,
+    ) -> Vec<DeadCode> {
+        let mut results = Vec::new();
+        self.scan(node, index, &mut results, cache);
+
+        results.sort_by_key(|result| result.binding.name.text_range().start());
         results
     }
 
@@ -66,68 +165,402 @@ impl Settings {
     fn scan(
         &self,
         node: &SyntaxNode<NixLanguage>,
-        dead: &mut HashSet<SyntaxNode<NixLanguage>>,
-        results: &mut HashMap<SyntaxNode<NixLanguage>, DeadCode>,
+        index: &Index,
+        results: &mut Vec<DeadCode>,
+        mut cache: Option<&mut ScopeCache>,
     ) {
         // check the scope of this `node`
         if let Some(scope) = Scope::new(node) {
-            if !(self.no_lambda_arg && scope.is_lambda_arg()) {
-                for binding in scope.bindings() {
-                    if self.no_underscore && binding.starts_with_underscore() {
-                        continue;
-                    }
-                    if self.no_lambda_pattern_names && scope.is_lambda_pattern_name(&binding.name) {
-                        continue;
-                    }
-
-                    if binding.is_mortal() && !binding.has_pragma_skip() {
-                        let unused = scope.bodies().all(|body|
-                            // remove this binding's own node
-                            body == binding.decl_node
-                            // excluding already unused results
-                            || dead.contains(&body)
-                            || is_dead_inherit(dead, &body)
-                            // or not used anywhere
-                            || ! usage::find(&binding.name, &body));
-                        if unused
-                            || (self.warn_used_underscore
-                                && binding.name.syntax().text().char_at(0.into()) == Some('_')
-                                && !unused)
-                        {
-                            dead.insert(binding.decl_node.clone());
-                            results.insert(
-                                binding.decl_node.clone(),
-                                DeadCode {
-                                    scope: scope.clone(),
-                                    binding,
-                                    unused,
-                                },
-                            );
-                        }
-                    }
+            match &scope {
+                Scope::With(with) => self.scan_with(with, index, results),
+                Scope::RecAttrSet(attr_set) => {
+                    self.scan_rec(attr_set, index, results);
+                    self.scan_shadowing(&scope, index, results);
+                }
+                _ if self.no_lambda_arg && scope.is_lambda_arg() => {}
+                _ => {
+                    self.scan_scope(node, &scope, index, results, cache.as_deref_mut());
+                    self.scan_shadowing(&scope, index, results);
                 }
             }
         }
 
         // recurse through the AST
         for child in node.children() {
-            self.scan(&child, dead, results);
+            self.scan(&child, index, results, cache.as_deref_mut());
+        }
+    }
+
+    /// Find the dead bindings of one `scope` that is declared by `node`
+    fn scan_scope(
+        &self,
+        node: &SyntaxNode<NixLanguage>,
+        scope: &Scope,
+        index: &Index,
+        results: &mut Vec<DeadCode>,
+        cache: Option<&mut ScopeCache>,
+    ) {
+        let bindings = scope.bindings().collect::<Vec<_>>();
+        let checked = bindings
+            .iter()
+            .map(|binding| self.is_checked(scope, binding, index))
+            .collect::<Vec<_>>();
+
+        // bindings that are never checked are alive from the start
+        let passed = passed_through(scope, &bindings, index);
+        let initial = checked
+            .iter()
+            .zip(passed)
+            .map(|(checked, passed)| !checked || passed)
+            .collect::<Vec<_>>();
+        let alive = match cache {
+            Some(cache) => cache.alive(node, initial, |initial| {
+                reachable(scope, &bindings, initial, index)
+            }),
+            None => reachable(scope, &bindings, initial, index),
+        };
+
+        for ((binding, checked), alive) in bindings.into_iter().zip(checked).zip(alive) {
+            // the `body` of `let { ... }` is the result, not selected from
+            if alive
+                && binding.is_mortal()
+                && self.warn_unused_attrs
+                && matches!(scope, Scope::LetIn(_) | Scope::LegacyLet(_))
+            {
+                self.scan_let_attrs(&binding, index, results);
+            }
+            if !checked {
+                continue;
+            }
+            let unused = !alive;
+            if unused || (self.warn_used_underscore && binding.starts_with_underscore()) {
+                results.push(DeadCode {
+                    scope: scope.clone(),
+                    binding,
+                    shadowed: None,
+                    unused,
+                });
+            }
+        }
+    }
+
+    /// Report the attributes of a `let name = { ... };` that are
+    /// never selected from `name`
+    fn scan_let_attrs(&self, binding: &Binding, index: &Index, results: &mut Vec<DeadCode>) {
+        let Some(entry) = AttrpathValue::cast(binding.decl_node.clone()) else {
+            return;
+        };
+        if entry
+            .attrpath()
+            .map_or(0, |attrpath| attrpath.attrs().count())
+            != 1
+        {
+            return;
+        }
+        let Some(Expr::AttrSet(attr_set)) = entry.value() else {
+            return;
+        };
+        if attr_set.rec_token().is_some() {
+            return;
+        }
+        let Some(selected) = selected_attrs(index.references(binding)) else {
+            return;
+        };
+
+        let scope = Scope::LetAttrSet(attr_set);
+        for attr in scope.bindings() {
+            if selected.contains(attr.name.as_str())
+                || (self.no_underscore && attr.starts_with_underscore())
+                || attr.has_pragma_skip()
+            {
+                continue;
+            }
+            results.push(DeadCode {
+                scope: scope.clone(),
+                binding: attr,
+                shadowed: None,
+                unused: true,
+            });
+        }
+    }
+
+    /// Report a `with` whose namespace cannot provide any variable
+    fn scan_with(&self, with: &With, index: &Index, results: &mut Vec<DeadCode>) {
+        if !self.warn_unused_with {
+            return;
+        }
+        let Some(namespace) = with.namespace() else {
+            return;
+        };
+        let binding = Binding::new(
+            Name::new(namespace.syntax().clone()),
+            with.syntax().clone(),
+            true,
+        );
+        if !index.is_with_used(with.syntax()) && !binding.has_pragma_skip() {
+            results.push(DeadCode {
+                scope: Scope::With(with.clone()),
+                binding,
+                shadowed: None,
+                unused: true,
+            });
+        }
+    }
+
+    /// Report a `rec { ... }` whose entries do not reference any of
+    /// its keys
+    fn scan_rec(&self, attr_set: &AttrSet, index: &Index, results: &mut Vec<DeadCode>) {
+        if !self.warn_unused_rec {
+            return;
+        }
+        let Some(rec) = attr_set.rec_token() else {
+            return;
+        };
+
+        let scope = Scope::RecAttrSet(attr_set.clone());
+        let is_recursive = scope.bindings().any(|binding| {
+            index.references(&binding).iter().any(|reference| {
+                // its own `inherit name;` takes `name` from the enclosing
+                // scope, unlike those of nested scopes
+                reference.ident.parent().is_none_or(|parent| {
+                    parent.kind() != SyntaxKind::NODE_INHERIT
+                        || parent.parent().as_ref() != Some(attr_set.syntax())
+                })
+            })
+        });
+        let binding = Binding::new(Name::from_token(&rec), attr_set.syntax().clone(), true);
+        if !is_recursive && !binding.has_pragma_skip() {
+            results.push(DeadCode {
+                scope,
+                binding,
+                shadowed: None,
+                unused: true,
+            });
         }
     }
+
+    /// Report the bindings of `scope` that shadow a binding of an
+    /// enclosing scope
+    ///
+    /// `inherit name;` is not reported as it passes on the outer
+    /// binding, neither are anonymous lambda arguments like `_`.
+    fn scan_shadowing(&self, scope: &Scope, index: &Index, results: &mut Vec<DeadCode>) {
+        if !self.warn_shadowing {
+            return;
+        }
+
+        for binding in scope.bindings() {
+            let Some(shadowed) = index.shadowed(&binding) else {
+                continue;
+            };
+            let is_inherit = binding
+                .decl_node
+                .parent()
+                .and_then(Inherit::cast)
+                .is_some_and(|inherit| inherit.from().is_none());
+            if is_inherit
+                || (scope.is_lambda_arg() && binding.starts_with_underscore())
+                || (self.no_underscore && binding.starts_with_underscore())
+                || self.is_interface_name(scope, &binding, index)
+                || binding.has_pragma_skip()
+            {
+                continue;
+            }
+
+            results.push(DeadCode {
+                scope: scope.clone(),
+                binding,
+                shadowed: Some(shadowed.clone()),
+                unused: false,
+            });
+        }
+    }
+
+    /// Is `binding` subject to be reported at all?
+    fn is_checked(&self, scope: &Scope, binding: &Binding, index: &Index) -> bool {
+        if self.no_underscore && binding.starts_with_underscore() {
+            return false;
+        }
+        if self.is_interface_name(scope, binding, index) {
+            return false;
+        }
+
+        binding.is_mortal() && !binding.has_pragma_skip()
+    }
+
+    /// Is `binding` a lambda pattern name that callers may rely on,
+    /// either by [`Settings::no_lambda_pattern_names`],
+    /// [`Settings::no_top_lambda_pattern_names`] or because the lambda
+    /// is passed to `callPackage`, `functionArgs` and the like?
+    fn is_interface_name(&self, scope: &Scope, binding: &Binding, index: &Index) -> bool {
+        let Scope::LambdaPattern(pattern, _) = scope else {
+            return false;
+        };
+        scope.is_lambda_pattern_name(&binding.name)
+            && (self.no_lambda_pattern_names
+                || (self.no_top_lambda_pattern_names && scope.is_top_lambda_pattern())
+                || index.is_reflected(pattern))
+    }
+}
+
+/// Which of the `bindings` of `scope` are reachable from its roots,
+/// starting with the `initial`ly alive ones
+fn reachable(scope: &Scope, bindings: &[Binding], initial: Vec<bool>, index: &Index) -> Vec<bool> {
+    let bodies = scope
+        .bodies()
+        .enumerate()
+        .map(|(i, body)| (body, i))
+        .collect::<HashMap<_, _>>();
+    // the bindings that each body belongs to, and references
+    let mut owners = vec![Vec::new(); bodies.len()];
+    let mut references = vec![Vec::new(); bodies.len()];
+    for (i, binding) in bindings.iter().enumerate() {
+        if let Some(&body) = bodies.get(&decl_body(binding)) {
+            owners[body].push(i);
+        }
+        for reference in index.references(binding) {
+            if let Some(&body) = bodies.get(&reference.body) {
+                references[body].push(i);
+            }
+        }
+    }
+    let mut owned = vec![Vec::new(); bindings.len()];
+    for (body, owners) in owners.iter().enumerate() {
+        for &owner in owners {
+            owned[owner].push(body);
+        }
+    }
+
+    // bodies that are owned by no binding, or by any alive one, are
+    // roots: the `(source)` of `inherit (source) a b;` is shared
+    let mut alive = initial;
+    let mut pending = (0..bodies.len())
+        .filter(|&body| owners[body].is_empty() || owners[body].iter().any(|&owner| alive[owner]))
+        .collect::<Vec<_>>();
+    let mut visited = vec![false; bodies.len()];
+    while let Some(body) = pending.pop() {
+        if visited[body] {
+            continue;
+        }
+        visited[body] = true;
+
+        for &binding in &references[body] {
+            if !alive[binding] {
+                alive[binding] = true;
+                pending.extend_from_slice(&owned[binding]);
+            }
+        }
+    }
+
+    alive
+}
+
+/// Which of the `bindings` of a `{ ... }@args: ...` are used through
+/// `args`
+///
+/// All pattern names are if `args` escapes as a whole, like with
+/// `f args`, `args // { ... }`, `inherit args;` or `[ args ]`: any use
+/// other than a [`selection()`]. Otherwise only those that are selected
+/// with `args.name`, or consumed by a file that is resolved for
+/// `import ./file.nix args`.
+fn passed_through(scope: &Scope, bindings: &[Binding], index: &Index) -> Vec<bool> {
+    let mut passed = vec![false; bindings.len()];
+    let Scope::LambdaPattern(pattern, _) = scope else {
+        return passed;
+    };
+    let Some(pat_bind) = pattern.pat_bind().and_then(|pat_bind| pat_bind.ident()) else {
+        return passed;
+    };
+    let Some(args) = bindings
+        .iter()
+        .find(|binding| binding.decl_node == *pat_bind.syntax())
+    else {
+        return passed;
+    };
+
+    // `None` if all names are passed on
+    let mut selected = Some(HashSet::new());
+    for reference in index.references(args) {
+        // `import ./file.nix args` that has been resolved
+        if let (Some(consumed), Some(selected)) =
+            (index.imported(&reference.ident), selected.as_mut())
+        {
+            selected.extend(consumed.iter().cloned());
+            continue;
+        }
+        let Some(attrs) = selection(reference) else {
+            selected = None;
+            break;
+        };
+        for attr in attrs {
+            match (Name::from_attr(&attr), selected.as_mut()) {
+                (Some(name), Some(selected)) => {
+                    selected.insert(name.as_str().to_string());
+                }
+                // `args.${...}`
+                _ => selected = None,
+            }
+        }
+    }
+
+    for (passed, binding) in passed.iter_mut().zip(bindings) {
+        *passed = scope.is_lambda_pattern_name(&binding.name)
+            && selected
+                .as_ref()
+                .is_none_or(|selected| selected.contains(binding.name.as_str()));
+    }
+    passed
 }
 
-/// is node body (`InheritFrom`) of an inherit clause that contains only dead bindings?
-fn is_dead_inherit(
-    dead: &HashSet<SyntaxNode<NixLanguage>>,
-    node: &SyntaxNode<NixLanguage>,
-) -> bool {
-    if node.kind() != SyntaxKind::NODE_INHERIT_FROM {
-        return false;
+/// The attributes that `reference` selects from its variable:
+/// `name.attr`, `name ? attr`, `inherit (name) attr`
+///
+/// `None` if the variable is used in any other way.
+fn selection(reference: &Reference) -> Option<Vec<Attr>> {
+    let parent = reference.ident.parent()?;
+    match parent.kind() {
+        SyntaxKind::NODE_SELECT => {
+            let select = Select::cast(parent)?;
+            if *select.expr()?.syntax() != reference.ident {
+                return None;
+            }
+            Some(select.attrpath()?.attrs().take(1).collect())
+        }
+        SyntaxKind::NODE_HAS_ATTR => {
+            let has_attr = HasAttr::cast(parent)?;
+            if *has_attr.expr()?.syntax() != reference.ident {
+                return None;
+            }
+            Some(has_attr.attrpath()?.attrs().take(1).collect())
+        }
+        SyntaxKind::NODE_INHERIT_FROM => Some(Inherit::cast(parent.parent()?)?.attrs().collect()),
+        _ => None,
     }
+}
+
+/// Names of the attributes that are selected from a variable by its
+/// `references`, see [`selection()`]
+///
+/// `None` if the variable is used in any other way, so that its value
+/// escapes and every attribute may be used.
+fn selected_attrs(references: &[Reference]) -> Option<HashSet<String>> {
+    let mut selected = HashSet::new();
+    for reference in references {
+        for attr in selection(reference)? {
+            selected.insert(Name::from_attr(&attr)?.as_str().to_string());
+        }
+    }
+    Some(selected)
+}
 
-    if let Some(inherit) = node.parent().and_then(Inherit::cast) {
-        inherit.attrs().all(|attr| dead.contains(attr.syntax()))
-    } else {
-        false
+/// The scope body that belongs to the declaration of `binding`: the
+/// declaration itself, or the `inherit` clause (resp. its `(...)`
+/// source) that it is part of
+fn decl_body(binding: &Binding) -> SyntaxNode<NixLanguage> {
+    match binding.decl_node.parent().and_then(Inherit::cast) {
+        Some(inherit) => inherit
+            .from()
+            .map_or_else(|| inherit.syntax().clone(), |from| from.syntax().clone()),
+        None => binding.decl_node.clone(),
     }
 }
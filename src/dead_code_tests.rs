@@ -1,7 +1,6 @@
 #![cfg(test)]
 
 use crate::dead_code::{DeadCode, Settings};
-use rowan::ast::AstNode;
 
 fn run_settings(content: &str, settings: &Settings) -> Vec<DeadCode> {
     let ast = rnix::Root::parse(content);
@@ -113,6 +112,26 @@ fn let_in_inherit_shadowed() {
     );
 }
 
+#[test]
+fn scope_inherits_from() {
+    let ast = rnix::Root::parse(
+        "let x = 1; inherit (x) a; inherit b \"c\"; inherit (let d = 1; in d) e; in a",
+    );
+    let scope = ast
+        .syntax()
+        .descendants()
+        .find_map(|node| crate::Scope::new(&node))
+        .unwrap();
+    let inherits =
+        |name: &str| scope.inherits_from(&crate::Name::new(rnix::Root::parse(name).syntax()));
+    assert!(inherits("x"));
+    assert!(inherits("b"));
+    assert!(inherits("c"));
+    assert!(!inherits("a"));
+    // bound inside of the inherited expression
+    assert!(!inherits("d"));
+}
+
 #[test]
 fn lambda_arg_alive() {
     let results = run("alive: alive");
@@ -174,8 +193,7 @@ fn lambda_pattern_alive() {
 #[test]
 fn lambda_pattern_dead_ellipsis_alias() {
     let results = run("alive@{ dead, ... }: alive");
-    assert_eq!(1, results.len());
-    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(0, results.len());
 }
 
 #[test]
@@ -188,8 +206,7 @@ fn lambda_pattern_dead_simple() {
 #[test]
 fn lambda_pattern_alias() {
     let results = run("{ dead }@args: args");
-    assert_eq!(1, results.len());
-    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(0, results.len());
 }
 
 #[test]
@@ -283,6 +300,34 @@ alive
     assert_eq!(0, results.len());
 }
 
+#[test]
+fn skip_inherit_from_source() {
+    let results = run("
+let
+  y = {};
+  inherit (y)
+    # deadnix: skip
+    a
+    b;
+in 1
+    ");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "b");
+}
+
+#[test]
+fn no_underscore_inherit_from_source() {
+    let results = run_settings(
+        "let y = {}; inherit (y) _a b; in 1",
+        &Settings {
+            no_underscore: true,
+            ..Settings::default()
+        },
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "b");
+}
+
 #[test]
 fn skip_lambda_arg() {
     let results = run("
@@ -522,3 +567,808 @@ fn ident_is_no_var() {
     assert_eq!(1, results.len());
     assert_eq!(results[0].binding.name.to_string(), "foo");
 }
+
+#[test]
+fn let_in_dead_cycle() {
+    let results = run("let dead1 = dead2; dead2 = dead1; in false");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn let_in_dead_cycle_alive_neighbour() {
+    let results = run("let alive = 1; dead1 = dead2 + alive; dead2 = dead1; in alive");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn let_in_alive_cycle() {
+    let results = run("let alive1 = alive2; alive2 = alive1; in alive1");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_inherit_dead_cycle() {
+    let results = run("let inherit (dead2) dead1; inherit (dead1) dead2; in false");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn lambda_pattern_dead_cycle() {
+    let results = run("{ dead1 ? dead2, dead2 ? dead1 }: false");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn rec_attrset_dead_cycle() {
+    let results = run("let dead1 = dead2; dead2 = rec { x = dead1; }; in false");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+fn run_with(content: &str) -> Vec<DeadCode> {
+    run_settings(
+        content,
+        &Settings {
+            warn_unused_with: true,
+            ..Settings::default()
+        },
+    )
+}
+
+#[test]
+fn with_alive() {
+    let results = run_with("with pkgs; hello");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn with_dead() {
+    let results = run_with("{ hello }: with pkgs; hello");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "pkgs");
+}
+
+#[test]
+fn with_dead_select() {
+    let results = run_with("let x = 1; in with pkgs.lib; [ x ]");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "pkgs.lib");
+}
+
+#[test]
+fn with_dead_attrset_keys() {
+    let results = run_with("with lib; { foo = 1; bar.baz = 2; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "lib");
+}
+
+#[test]
+fn with_alive_string_splice() {
+    let results = run_with("with lib; { \"${foo}\" = 1; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn with_alive_nested() {
+    let results = run_with("with lib; with pkgs; hello");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn with_dead_nested() {
+    let results = run_with("{ pkgs }: with lib; with pkgs; 23");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "lib");
+    assert_eq!(results[1].binding.name.to_string(), "pkgs");
+}
+
+#[test]
+fn with_alive_by_inner_namespace() {
+    let results = run_with("with pkgs; with lib; 23");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "lib");
+}
+
+#[test]
+fn with_alive_inherit() {
+    let results = run_with("let x = 1; in with pkgs; { inherit hello; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "x");
+}
+
+#[test]
+fn with_shadowed_by_let() {
+    let results = run_with("with pkgs; let hello = 1; in hello");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "pkgs");
+}
+
+#[test]
+fn with_skip() {
+    let results = run_with(
+        "
+# deadnix: skip
+with pkgs; 23
+    ",
+    );
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn with_disabled() {
+    let results = run_settings(
+        "with pkgs; 23",
+        &Settings {
+            warn_unused_with: false,
+            ..Settings::default()
+        },
+    );
+    assert_eq!(0, results.len());
+}
+
+fn run_rec(content: &str) -> Vec<DeadCode> {
+    run_settings(
+        content,
+        &Settings {
+            warn_unused_rec: true,
+            ..Settings::default()
+        },
+    )
+}
+
+#[test]
+fn rec_attrset_needed() {
+    let results = run_rec("rec { a = 1; b = a; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_unused() {
+    let results = run_rec("rec { a = 1; b = 2; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "rec");
+    assert_eq!(results[0].to_string(), "Superfluous rec attrset");
+}
+
+#[test]
+fn rec_attrset_unused_not_warned() {
+    let results = run("rec { a = 1; b = 2; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_unused_outer_reference() {
+    let results = run_rec("{ a }: rec { b = a; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "rec");
+}
+
+#[test]
+fn rec_attrset_unused_inherit() {
+    let results = run_rec("{ a }: rec { inherit a; b = 2; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "rec");
+}
+
+#[test]
+fn rec_attrset_needed_nested_inherit() {
+    let results = run_rec("rec { a = 1; b = { inherit a; }; }");
+    assert_eq!(0, results.len());
+    let results = run_rec("rec { a = 1; b = let inherit a; in a; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_needed_inherit_from() {
+    let results = run_rec("rec { a = {}; inherit (a) b; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_needed_nested() {
+    let results =
+        run_rec("mkDerivation rec { version = \"1\"; src = { url = \"x-${version}\"; }; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_unused_shadowed() {
+    let results = run_rec("rec { a = 1; b = let a = 2; in a; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "rec");
+}
+
+#[test]
+fn rec_attrset_skip() {
+    let results = run_rec(
+        "
+# deadnix: skip
+rec { a = 1; }
+    ",
+    );
+    assert_eq!(0, results.len());
+}
+
+fn run_attrs(content: &str) -> Vec<DeadCode> {
+    run_settings(
+        content,
+        &Settings {
+            warn_unused_attrs: true,
+            ..Settings::default()
+        },
+    )
+}
+
+#[test]
+fn let_attrs_select() {
+    let results = run_attrs("let cfg = { alive = 1; dead = 2; }; in cfg.alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(results[0].to_string(), "Unused let attribute: dead");
+}
+
+#[test]
+fn let_attrs_not_warned() {
+    let results = run("let cfg = { alive = 1; dead = 2; }; in cfg.alive");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_inherit_from() {
+    let results = run_attrs("let cfg = { alive = 1; dead = 2; }; inherit (cfg) alive; in alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn let_attrs_has_attr_or_default() {
+    let results = run_attrs(
+        "
+      let
+        cfg = { alive1 = 1; alive2.x = 2; dead = 3; inherit dead2; };
+      in [ (cfg ? alive1) (cfg.alive2.x or 0) ]
+    ",
+    );
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn let_attrs_escape_apply() {
+    let results = run_attrs("let cfg = { alive = 1; also = 2; }; in f cfg");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_escape_update() {
+    let results = run_attrs("let cfg = { alive = 1; also = 2; }; in cfg.alive + (cfg // {}).x");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_escape_inherit() {
+    let results = run_attrs("let cfg = { alive = 1; also = 2; }; in { inherit cfg; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_escape_default() {
+    let results = run_attrs("let cfg = { alive = 1; also = 2; }; in x.y or cfg");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_escape_dynamic() {
+    let results = run_attrs("let cfg = { alive = 1; also = 2; }; in cfg.${x}");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_shadowed() {
+    let results =
+        run_attrs("let cfg = { alive = 1; dead = 2; }; in cfg.alive + (cfg: cfg.dead) {}");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn let_attrs_dead_binding() {
+    let results = run_attrs("let cfg = { dead = 2; }; in 1");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "cfg");
+}
+
+#[test]
+fn let_attrs_rec() {
+    let results = run_attrs("let cfg = rec { alive = 1; also = alive; }; in cfg.alive");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_string_alive() {
+    let results = run("let \"alive\" = 1; in alive");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_string_dead() {
+    let results = run("let \"dead\" = 1; alive = 2; in alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(results[0].to_string(), "Unused let binding: dead");
+}
+
+#[test]
+fn let_string_escaped() {
+    let results = run(r#"let "dead\"1" = 1; in 2"#);
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead\"1");
+}
+
+#[test]
+fn let_string_interpolated() {
+    let results = run("let \"${alive}\" = 1; in 2");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_string_shadowed() {
+    let results = run("let dead = 1; in let \"dead\" = 2; in dead");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(usize::from(results[0].binding.name.text_range().start()), 4);
+}
+
+#[test]
+fn let_inherit_string_dead() {
+    let results = run("let inherit (x) \"dead\" alive; in alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn let_inherit_string_alive() {
+    let results = run("let alive = 1; in { inherit \"alive\"; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_inherit_string_outer_alive() {
+    let results = run("let alive = 1; in let inherit \"alive\"; in alive");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn rec_attrset_string_needed() {
+    let results = run_rec("rec { \"a\" = 1; b = a; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn let_attrs_string_select() {
+    let results = run_attrs("let cfg = { \"alive\" = 1; dead = 2; }; in cfg.\"alive\"");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+fn run_shadowing(content: &str) -> Vec<DeadCode> {
+    run_settings(
+        content,
+        &Settings {
+            warn_shadowing: true,
+            ..Settings::default()
+        },
+    )
+}
+
+#[test]
+fn shadowing_lambda_arg() {
+    let results = run_shadowing("{ pkgs }: map (pkgs: pkgs.hello) pkgs");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "pkgs");
+    assert_eq!(results[0].to_string(), "Shadowing lambda argument: pkgs");
+    let (scope, shadowed) = results[0].shadowed.as_ref().unwrap();
+    assert_eq!(scope.to_string(), "lambda pattern");
+    assert_eq!(usize::from(shadowed.name.text_range().start()), 2);
+}
+
+#[test]
+fn shadowing_not_warned() {
+    let results = run("{ pkgs }: map (pkgs: pkgs.hello) pkgs");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn shadowing_let_and_unused() {
+    let results = run_shadowing("let dead = true; in let dead = false; in dead");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].to_string(), "Unused let binding: dead");
+    assert_eq!(results[1].to_string(), "Shadowing let binding: dead");
+    assert_eq!(
+        usize::from(results[1].binding.name.text_range().start()),
+        24
+    );
+}
+
+#[test]
+fn shadowing_pattern_entry() {
+    let results = run_shadowing("lib: { lib, ... }: lib");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].to_string(), "Unused lambda argument: lib");
+    assert_eq!(results[1].to_string(), "Shadowing lambda pattern: lib");
+}
+
+#[test]
+fn shadowing_rec_attr() {
+    let results = run_shadowing("x: rec { x = 1; y = x; }");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].to_string(), "Unused lambda argument: x");
+    assert_eq!(results[1].to_string(), "Shadowing rec attrset: x");
+}
+
+#[test]
+fn shadowing_same_scope() {
+    let results = run_shadowing("let foo.a = 1; foo.b = 2; in foo");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn shadowing_attrset_no_scope() {
+    let results = run_shadowing("pkgs: { pkgs = pkgs; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn shadowing_inherit() {
+    let results = run_shadowing("lib: let inherit lib; in lib");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn shadowing_inherit_from() {
+    let results = run_shadowing("lib: let inherit (lib.x) lib; in lib");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].to_string(), "Shadowing let binding: lib");
+}
+
+#[test]
+fn shadowing_underscore_arg() {
+    let results = run_shadowing("_: _: 1");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn shadowing_skip() {
+    let results = run_shadowing(
+        "
+pkgs:
+# deadnix: skip
+pkgs: pkgs
+    ",
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].to_string(), "Unused lambda argument: pkgs");
+}
+
+#[test]
+fn legacy_let_alive() {
+    let results = run("let { alive = 1; body = alive; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn legacy_let_dead() {
+    let results = run("let { alive = 1; dead = 2; body = alive; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(results[0].to_string(), "Unused legacy let binding: dead");
+}
+
+#[test]
+fn legacy_let_loop_dead() {
+    let results = run("let { dead1 = dead2; dead2 = dead1; body = 23; }");
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead1");
+    assert_eq!(results[1].binding.name.to_string(), "dead2");
+}
+
+#[test]
+fn legacy_let_inherit_dead() {
+    let results = run("let { inherit (x) alive dead; body = alive; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn legacy_let_shadowing() {
+    let results = run("let dead = 1; in let { dead = 2; body = dead; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+    assert_eq!(results[0].to_string(), "Unused let binding: dead");
+}
+
+#[test]
+fn legacy_let_body_attrs() {
+    let results = run_attrs("let { body = { a = 1; b = 2; }; }");
+    assert_eq!(0, results.len());
+
+    let results = run_attrs("let { cfg = { alive = 1; dead = 2; }; body = { a = cfg.alive; }; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn reflected_call_package_literal() {
+    let results = run("callPackage ({ alive, dead }: alive) { }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn reflected_call_package_let() {
+    let results = run("let pkg = { alive, dead }: alive; in pkgs.callPackage pkg { }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn reflected_function_args() {
+    let results =
+        run("{ lib }: [ (lib.functionArgs ({ a, b ? 1 }: a)) (builtins.functionArgs ({ c }: 1)) ]");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn reflected_make_overridable() {
+    let results = run("lib: lib.makeOverridable (({ alive, dead }: alive)) { }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn reflected_other_lambdas_checked() {
+    let results =
+        run("let f = { alive, dead }: alive; in [ (f { }) (callPackage ({ a, b }: a) { }) ]");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn reflected_pattern_bind_checked() {
+    let results = run("callPackage (args@{ alive, dead }: alive) { }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "args");
+}
+
+#[test]
+fn reflected_other_function_checked() {
+    let results = run("import ({ alive, dead }: alive) { }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+fn run_top_lambda(content: &str) -> Vec<DeadCode> {
+    run_settings(
+        content,
+        &Settings {
+            no_top_lambda_pattern_names: true,
+            ..Settings::default()
+        },
+    )
+}
+
+#[test]
+fn top_lambda_pattern_exempt() {
+    let results = run_top_lambda("{ alive, dead }: alive");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn top_lambda_pattern_parens_comment() {
+    let results = run_top_lambda("# comment\n(({ alive, dead }: alive))");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn top_lambda_pattern_nested_checked() {
+    let results = run_top_lambda("{ alive, dead }: { foo, bar }: alive foo");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "bar");
+}
+
+#[test]
+fn top_lambda_pattern_not_top() {
+    let results = run_top_lambda("let f = { alive, dead }: alive; in f");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn top_lambda_pattern_bind_checked() {
+    let results = run_top_lambda("args@{ alive, dead }: alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "args");
+}
+
+#[test]
+fn lambda_pattern_args_import() {
+    let results = run("{ a, b, ... }@args: import ./impl.nix args");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn lambda_pattern_args_apply_parens() {
+    let results = run("args@{ a, b ? a }: f (args)");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn lambda_pattern_args_update() {
+    let results = run("{ a, b }@args: args // { c = 1; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn lambda_pattern_args_inherit() {
+    let results = run("{ a, b }@args: { inherit args; }");
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn lambda_pattern_args_select() {
+    let results = run("{ alive, dead }@args: args.alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn lambda_pattern_args_inherit_from() {
+    let results = run("{ alive, dead }@args: { inherit (args) alive; }");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn lambda_pattern_args_escape() {
+    for content in [
+        "{ a, b, ... }@args: [ args ]",
+        "{ a, b, ... }@args: { x = args; }",
+        "{ a, b, ... }@args: if c then args else {}",
+        "{ a, b }@args: args { }",
+    ] {
+        assert_eq!(0, run(content).len(), "{content}");
+    }
+}
+
+#[test]
+fn lambda_pattern_args_has_attr() {
+    let results = run("{ alive, dead }@args: args ? alive");
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+fn run_imports(content: &str, files: &[(&str, &str)]) -> Vec<DeadCode> {
+    run_imports_settings(content, files, &Settings::default())
+}
+
+fn run_imports_settings(
+    content: &str,
+    files: &[(&str, &str)],
+    settings: &Settings,
+) -> Vec<DeadCode> {
+    let mut imports = crate::Imports::new();
+    for (path, content) in files {
+        let ast = rnix::Root::parse(content);
+        assert_eq!(0, ast.errors().len());
+        imports.add(std::path::Path::new(path), &ast.syntax());
+    }
+
+    let ast = rnix::Root::parse(content);
+    assert_eq!(0, ast.errors().len());
+    settings.find_dead_code_with_imports(
+        &ast.syntax(),
+        std::path::Path::new("/nonexistent/caller.nix"),
+        &imports,
+    )
+}
+
+#[test]
+fn imports_consumed() {
+    let results = run_imports(
+        "{ alive, dead, ... }@args: import ./impl.nix args",
+        &[("/nonexistent/impl.nix", "{ alive, ... }: alive")],
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn imports_unused_by_callee() {
+    let results = run_imports(
+        "{ alive, dead, ... }@args: import ./impl.nix args",
+        &[("/nonexistent/impl.nix", "{ alive, dead, ... }: alive")],
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn imports_unresolved() {
+    let results = run_imports(
+        "{ alive1, alive2, ... }@args: import ./other.nix args",
+        &[("/nonexistent/impl.nix", "{ alive1, ... }: alive1")],
+    );
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn imports_no_pattern() {
+    let results = run_imports(
+        "{ alive1, alive2, ... }@args: import ./impl.nix args",
+        &[("/nonexistent/impl.nix", "args: args")],
+    );
+    assert_eq!(0, results.len());
+}
+
+#[test]
+fn imports_combined_with_select() {
+    let results = run_imports(
+        "{ alive1, alive2, dead }@args: [ (import ./impl.nix args) args.alive2 ]",
+        &[("/nonexistent/impl.nix", "{ alive1, ... }: alive1")],
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn imports_used_underscore() {
+    // `_a` is consumed by the callee even though it is reported there
+    // with `-W`
+    let results = run_imports_settings(
+        "{ _a, b, ... }@args: import ./impl.nix args",
+        &[("/nonexistent/impl.nix", "{ _a, b }: _a")],
+        &Settings {
+            warn_used_underscore: true,
+            ..Settings::default()
+        },
+    );
+    let summary = results
+        .iter()
+        .map(|result| (result.binding.name.to_string(), result.unused))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [("_a".to_string(), false), ("b".to_string(), true)]
+    );
+}
+
+#[test]
+fn imports_top_pattern_names_exempt() {
+    // the callee's pattern names are checked despite `-T`
+    let results = run_imports_settings(
+        "{ }: { f = { a, b, ... }@args: import ./impl.nix args; }",
+        &[("/nonexistent/impl.nix", "{ a, b }: a")],
+        &Settings {
+            no_top_lambda_pattern_names: true,
+            ..Settings::default()
+        },
+    );
+    assert_eq!(1, results.len());
+    assert_eq!(results[0].binding.name.to_string(), "b");
+}
+
+#[test]
+fn imports_other_passthrough() {
+    let results = run_imports(
+        "{ alive1, alive2 }@args: [ (import ./impl.nix args) (f args) ]",
+        &[("/nonexistent/impl.nix", "{ alive1, ... }: alive1")],
+    );
+    assert_eq!(0, results.len());
+}
@@ -0,0 +1,232 @@
+//! Unified diff of the changes of [`edit_dead_code()`](crate::edit_dead_code)
+
+use crate::{
+    dead_code::DeadCode,
+    edit::{applied, dead_code_edits, Edit},
+};
+use std::fmt::Write;
+
+/// Unchanged lines around changes
+const CONTEXT: usize = 3;
+
+/// Replacement of the 0-based `old` lines `start..end` with `new`
+struct Change<'a> {
+    start: usize,
+    end: usize,
+    new: Vec<&'a str>,
+}
+
+/// Unified diff of what [`edit_dead_code()`](crate::edit_dead_code)
+/// changes in `original` to remove `dead`
+///
+/// Empty if nothing would change. The file names in the header are
+/// `file` for both sides, as for `patch -p0`.
+///
+/// ```
+/// let content = "let\n  foo = 1;\nin 2\n";
+/// let ast = rnix::Root::parse(content);
+/// let results = deadnix::Settings::default().find_dead_code(&ast.syntax());
+/// assert_eq!(
+///     deadnix::diff_dead_code("test.nix", content, results.into_iter()),
+///     "--- test.nix\n+++ test.nix\n@@ -1,3 +1 @@\n-let\n-  foo = 1;\n-in 2\n+2\n",
+/// );
+/// ```
+pub fn diff_dead_code(file: &str, original: &str, dead: impl Iterator<Item = DeadCode>) -> String {
+    let dead = dead.collect::<Vec<_>>();
+    let edits = applied(dead_code_edits(original, &dead), |(_, edit)| edit);
+
+    let old_lines = original.split_inclusive('\n').collect::<Vec<_>>();
+    // byte offsets of the line starts, and of the end
+    let mut starts = Vec::with_capacity(old_lines.len() + 1);
+    let mut offset = 0;
+    for line in &old_lines {
+        starts.push(offset);
+        offset += line.len();
+    }
+    starts.push(offset);
+    let line_of = |offset: usize| starts.partition_point(|&start| start <= offset) - 1;
+
+    // edits that touch the same or adjacent lines
+    let mut groups: Vec<(usize, usize, Vec<&Edit>)> = Vec::new();
+    for (_, edit) in &edits {
+        let start = line_of(edit.start);
+        let end = line_of(edit.end.max(edit.start + 1) - 1) + 1;
+        match groups.last_mut() {
+            Some((_, group_end, group)) if start <= *group_end => {
+                *group_end = end;
+                group.push(edit);
+            }
+            _ => groups.push((start, end, vec![edit])),
+        }
+    }
+
+    let new_texts = groups
+        .iter()
+        .map(|(start, end, group)| {
+            let mut new = String::new();
+            let mut pos = starts[*start];
+            for edit in group {
+                new.push_str(&original[pos..edit.start]);
+                new.push_str(&edit.replacement);
+                pos = edit.end;
+            }
+            new.push_str(&original[pos..starts[*end]]);
+            new
+        })
+        .collect::<Vec<_>>();
+    let mut changes = Vec::new();
+    for ((start, end, _), new) in groups.iter().zip(&new_texts) {
+        let new = new.split_inclusive('\n').collect::<Vec<_>>();
+        split_change(*start, &old_lines[*start..*end], &new, &mut changes);
+    }
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = String::new();
+    writeln!(diff, "--- {file}").unwrap();
+    writeln!(diff, "+++ {file}").unwrap();
+    // numbers of added and removed lines before the current hunk
+    let mut added = 0;
+    let mut removed = 0;
+    let mut changes = changes.iter().peekable();
+    while let Some(first) = changes.next() {
+        let mut hunk = vec![first];
+        while let Some(next) =
+            changes.next_if(|next| next.start - hunk.last().expect("hunk").end <= 2 * CONTEXT)
+        {
+            hunk.push(next);
+        }
+
+        let start = first.start.saturating_sub(CONTEXT);
+        let end = (hunk.last().expect("hunk").end + CONTEXT).min(old_lines.len());
+        let old_count = end - start;
+        let hunk_added = hunk.iter().map(|change| change.new.len()).sum::<usize>();
+        let hunk_removed = hunk
+            .iter()
+            .map(|change| change.end - change.start)
+            .sum::<usize>();
+        let new_count = old_count + hunk_added - hunk_removed;
+        let new_start = start + added - removed;
+        writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            hunk_range(start, old_count),
+            hunk_range(new_start, new_count)
+        )
+        .unwrap();
+
+        let mut pos = start;
+        for change in hunk {
+            for line in &old_lines[pos..change.start] {
+                push_line(&mut diff, ' ', line);
+            }
+            for line in &old_lines[change.start..change.end] {
+                push_line(&mut diff, '-', line);
+            }
+            for line in &change.new {
+                push_line(&mut diff, '+', line);
+            }
+            pos = change.end;
+        }
+        for line in &old_lines[pos..end] {
+            push_line(&mut diff, ' ', line);
+        }
+        added += hunk_added;
+        removed += hunk_removed;
+    }
+    diff
+}
+
+/// Maximum number of old times new lines of a change for which the
+/// unchanged lines inside are found
+const MAX_MATCH: usize = 1 << 20;
+
+/// Add the changes that replace the `old` lines from `start` with
+/// `new`, leaving the lines of a longest common subsequence unchanged
+fn split_change<'a>(start: usize, old: &[&'a str], new: &[&'a str], changes: &mut Vec<Change<'a>>) {
+    // common prefix and suffix
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let start = start + prefix;
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    if old.is_empty() && new.is_empty() {
+        return;
+    }
+    if old.len() * new.len() > MAX_MATCH {
+        changes.push(Change {
+            start,
+            end: start + old.len(),
+            new: new.to_vec(),
+        });
+        return;
+    }
+
+    // lengths of the longest common subsequences of the suffixes
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut change: Option<Change> = None;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.extend(change.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let change = change.get_or_insert_with(|| Change {
+            start: start + i,
+            end: start + i,
+            new: Vec::new(),
+        });
+        if j == new.len()
+            || (i < old.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            i += 1;
+            change.end = start + i;
+        } else {
+            change.new.push(new[j]);
+            j += 1;
+        }
+    }
+    changes.extend(change);
+}
+
+/// `start,count` of a hunk header for the 0-based `start` line
+///
+/// An empty range starts at the line before it.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+fn push_line(diff: &mut String, prefix: char, line: &str) {
+    diff.push(prefix);
+    diff.push_str(line);
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
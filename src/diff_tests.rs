@@ -0,0 +1,178 @@
+#![cfg(test)]
+
+use crate::{diff::diff_dead_code, edit::edit_dead_code, Settings};
+
+fn diff(content: &str) -> String {
+    let ast = rnix::Root::parse(content);
+    assert_eq!(0, ast.errors().len());
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    diff_dead_code("test.nix", content, results.into_iter())
+}
+
+/// Apply a unified `diff` to `content`, checking the context
+fn patch(content: &str, diff: &str) -> String {
+    let old_lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    let mut lines = diff.split_inclusive('\n').peekable();
+    assert_eq!(lines.next(), Some("--- test.nix\n"));
+    assert_eq!(lines.next(), Some("+++ test.nix\n"));
+
+    let mut result = String::new();
+    let mut pos = 0;
+    while let Some(header) = lines.next() {
+        let old_start = header
+            .strip_prefix("@@ -")
+            .and_then(|header| header.split([',', ' ']).next())
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        // empty ranges start at the line before
+        let old_start = old_start.saturating_sub(1) + usize::from(header.starts_with("@@ -0,0"));
+        for line in &old_lines[pos..old_start] {
+            result.push_str(line);
+        }
+        pos = old_start;
+        while let Some(line) = lines.next_if(|line| !line.starts_with("@@")) {
+            let (prefix, text) = line.split_at(1);
+            let no_newline = lines
+                .next_if_eq(&"\\ No newline at end of file\n")
+                .is_some();
+            let text = if no_newline {
+                text.strip_suffix('\n').unwrap()
+            } else {
+                text
+            };
+            match prefix {
+                " " | "-" => {
+                    assert_eq!(old_lines[pos], text);
+                    pos += 1;
+                    if prefix == " " {
+                        result.push_str(text);
+                    }
+                }
+                "+" => result.push_str(text),
+                _ => panic!("unexpected line {line:?}"),
+            }
+        }
+    }
+    for line in &old_lines[pos..] {
+        result.push_str(line);
+    }
+    result
+}
+
+/// The diff applies to the result of `--edit`
+fn check(content: &str) -> String {
+    let diff = diff(content);
+    let ast = rnix::Root::parse(content);
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    let (edited, has_changes) = edit_dead_code(content, results.into_iter());
+    assert_eq!(has_changes, !diff.is_empty());
+    if has_changes {
+        assert_eq!(patch(content, &diff), edited);
+    }
+    diff
+}
+
+#[test]
+fn diff_none() {
+    assert_eq!(check("let a = 1; in a\n"), "");
+}
+
+#[test]
+fn diff_single_line() {
+    assert_eq!(
+        check("let\n  a = 1;\n  b = 2;\nin a\n"),
+        "--- test.nix
++++ test.nix
+@@ -1,4 +1,3 @@
+ let
+   a = 1;
+-  b = 2;
+ in a
+"
+    );
+}
+
+#[test]
+fn diff_changed_line() {
+    assert_eq!(
+        check("{\n  f = x: 1;\n}\n"),
+        "--- test.nix
++++ test.nix
+@@ -1,3 +1,3 @@
+ {
+-  f = x: 1;
++  f = _x: 1;
+ }
+"
+    );
+}
+
+#[test]
+fn diff_hunks() {
+    let lines = (0..20)
+        .map(|i| format!("  a{i} = a{};\n", i + 1))
+        .collect::<Vec<_>>()
+        .concat();
+    let content = format!("let\n  x = 1;\n{lines}  a20 = 1;\n  y = 2;\nin a0\n");
+    assert_eq!(
+        check(&content),
+        "--- test.nix
++++ test.nix
+@@ -1,5 +1,4 @@
+ let
+-  x = 1;
+   a0 = a1;
+   a1 = a2;
+   a2 = a3;
+@@ -21,5 +20,4 @@
+   a18 = a19;
+   a19 = a20;
+   a20 = 1;
+-  y = 2;
+ in a0
+"
+    );
+
+    // nearby changes share a hunk
+    let content = "let\n  x = 1;\n  a = 1;\n  b = 2;\n  c = 3;\n  y = 2;\nin a + b + c\n";
+    assert_eq!(check(content).matches("@@ -").count(), 1);
+}
+
+#[test]
+fn diff_let_removal() {
+    check("let\n  a = 1;\nin\n  x: 3");
+    check("{\n  a = let\n    b = 1;\n  in 2;\n  c = let d = 1; in 3;\n}\n");
+}
+
+#[test]
+fn diff_no_newline() {
+    assert_eq!(
+        check("x: 1"),
+        "--- test.nix
++++ test.nix
+@@ -1 +1 @@
+-x: 1
+\\ No newline at end of file
++_x: 1
+\\ No newline at end of file
+"
+    );
+}
+
+#[test]
+fn diff_unchanged_inside() {
+    // `c = h;` loses both line breaks around it, but is left unchanged
+    assert_eq!(
+        check("{ h }: let\n    a = h;\n    c = h;\n    f = 1;\n  in c\n"),
+        "--- test.nix
++++ test.nix
+@@ -1,5 +1,3 @@
+ { h }: let
+-    a = h;
+     c = h;
+-    f = 1;
+   in c
+"
+    );
+}
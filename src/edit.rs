@@ -5,11 +5,12 @@ use rnix::{
 };
 use rowan::{api::SyntaxNode, ast::AstNode};
 
-#[derive(Debug)]
-struct Edit {
-    start: usize,
-    end: usize,
-    replacement: String,
+/// Replacement of the byte range `start..end`
+#[derive(Debug, Clone)]
+pub(crate) struct Edit {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) replacement: String,
 }
 
 fn apply_edits<'a>(src: &str, edits: impl Iterator<Item = &'a Edit>) -> String {
@@ -26,41 +27,179 @@ fn apply_edits<'a>(src: &str, edits: impl Iterator<Item = &'a Edit>) -> String {
     result
 }
 
+/// Only the edits that [`apply_edits()`] does not skip because of
+/// overlapping an earlier one
+pub(crate) fn applied<T>(edits: Vec<T>, edit: impl Fn(&T) -> &Edit) -> Vec<T> {
+    let mut pos = 0;
+    edits
+        .into_iter()
+        .filter(|item| {
+            let edit = edit(item);
+            if pos <= edit.end {
+                pos = edit.end;
+                true
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
 /// Deletes `nodes` from content
 ///
 /// assumes `node` to be presorted
 pub fn edit_dead_code(original: &str, dead: impl Iterator<Item = DeadCode>) -> (String, bool) {
-    let mut edits = dead.filter_map(dead_to_edit).collect::<Vec<_>>();
-    edits.sort_unstable_by(|e1, e2| {
+    let dead = dead.collect::<Vec<_>>();
+    let edits = dead_code_edits(original, &dead);
+    let edited = apply_edits(original, edits.iter().map(|(_, edit)| edit));
+    (edited, !edits.is_empty())
+}
+
+/// The edits of [`edit_dead_code()`] in the `original` text, sorted
+///
+/// Each is paired with the index of the finding in `dead` that it
+/// removes. The removal of a `let ... in` or `inherit` that the
+/// removed findings leave empty includes their edits, and belongs to
+/// the first of them, or to none if it had already been empty.
+pub(crate) fn dead_code_edits(original: &str, dead: &[DeadCode]) -> Vec<(Option<usize>, Edit)> {
+    let mut edits = dead
+        .iter()
+        .enumerate()
+        .filter_map(|(index, dead_code)| Some((index, dead_to_edit(dead_code)?)))
+        .collect::<Vec<_>>();
+    edits.sort_unstable_by(|(_, e1), (_, e2)| {
         if e1.start == e2.start {
             e1.end.cmp(&e2.end)
         } else {
             e1.start.cmp(&e2.start)
         }
     });
+    let edits = applied(edits, |(_, edit)| edit);
 
-    let has_changes = !edits.is_empty();
-
-    let edited = apply_edits(original, edits.iter());
+    let edited = apply_edits(original, edits.iter().map(|(_, edit)| edit));
 
     // remove empty `let in`
     let ast = rnix::Root::parse(&edited);
     let mut let_in_edits = Vec::new();
     remove_empty_scopes(&ast.syntax(), &mut let_in_edits);
-    if let_in_edits.is_empty() {
-        (edited, has_changes)
-    } else {
-        (apply_edits(&edited, let_in_edits.iter()), true)
+    let let_in_edits = applied(let_in_edits, |edit| edit);
+
+    // map them into the original text, where they include the edits
+    // that they cover
+    let let_in_edits = let_in_edits
+        .into_iter()
+        .map(|let_in_edit| Edit {
+            start: original_offset(&edits, let_in_edit.start, true),
+            end: original_offset(&edits, let_in_edit.end, false),
+            replacement: let_in_edit.replacement,
+        })
+        .collect::<Vec<_>>();
+    let covers = |outer: &Edit, inner: &Edit| outer.start <= inner.start && inner.end <= outer.end;
+    let mut result = let_in_edits
+        .iter()
+        .map(|let_in_edit| {
+            let index = edits
+                .iter()
+                .filter(|(_, edit)| covers(let_in_edit, edit))
+                .map(|(index, _)| *index)
+                .min();
+            (index, let_in_edit.clone())
+        })
+        .collect::<Vec<_>>();
+    result.extend(
+        edits
+            .into_iter()
+            .filter(|(_, edit)| {
+                !let_in_edits
+                    .iter()
+                    .any(|let_in_edit| covers(let_in_edit, edit))
+            })
+            .map(|(index, edit)| (Some(index), edit)),
+    );
+    result.sort_unstable_by_key(|(_, edit)| (edit.start, edit.end));
+    result
+}
+
+/// The edits that remove each of `dead` on its own, by index
+///
+/// Like the [`dead_code_edits()`] of only that finding, without the
+/// removal of scopes that had already been empty. `edits` are the
+/// [`dead_code_edits()`] of all of `dead`, so that the text is parsed
+/// again only for the findings whose edit is part of the removal of a
+/// scope.
+#[cfg(feature = "json-out")]
+pub(crate) fn standalone_edits(
+    original: &str,
+    dead: &[DeadCode],
+    edits: &[(Option<usize>, Edit)],
+) -> Vec<Vec<Edit>> {
+    dead.iter()
+        .enumerate()
+        .map(|(index, dead_code)| {
+            let Some(edit) = dead_to_edit(dead_code) else {
+                return Vec::new();
+            };
+            if edits.iter().any(|(edit_index, other)| {
+                *edit_index == Some(index)
+                    && (other.start, other.end) == (edit.start, edit.end)
+                    && other.replacement == edit.replacement
+            }) {
+                return vec![edit];
+            }
+            dead_code_edits(original, std::slice::from_ref(dead_code))
+                .into_iter()
+                .filter(|(edit_index, _)| edit_index.is_some())
+                .map(|(_, edit)| edit)
+                .collect()
+        })
+        .collect()
+}
+
+/// The offset in the original text of an `offset` in the text after
+/// `edits`
+///
+/// An offset at the boundary of an edit is mapped so that the range
+/// it starts or ends covers the edit.
+fn original_offset(edits: &[(usize, Edit)], offset: usize, is_start: bool) -> usize {
+    // corresponding offsets in the original and edited text after the
+    // previous edit
+    let mut original = 0;
+    let mut edited = 0;
+    for (_, edit) in edits {
+        let edited_start = edited + (edit.start - original);
+        let edited_end = edited_start + edit.replacement.len();
+        if is_start {
+            if offset <= edited_start {
+                return original + (offset - edited);
+            } else if offset < edited_end {
+                return edit.start;
+            }
+        } else if offset < edited_start {
+            return original + (offset - edited);
+        } else if offset == edited_start && edited_start < edited_end {
+            return edit.start;
+        } else if offset <= edited_end {
+            return edit.end;
+        }
+        original = edit.end;
+        edited = edited_end;
     }
+    original + (offset - edited)
 }
 
-fn dead_to_edit(dead_code: DeadCode) -> Option<Edit> {
+/// The edit that removes `dead_code`, if there is one
+pub(crate) fn dead_to_edit(dead_code: &DeadCode) -> Option<Edit> {
+    if dead_code.shadowed.is_some() || !dead_code.unused {
+        // nothing to remove
+        return None;
+    }
+
     let range = dead_code.binding.decl_node.text_range();
     let mut start = usize::from(range.start());
     let mut end = usize::from(range.end());
     let mut replace_node = dead_code.binding.decl_node.clone();
     let mut replacement = None;
-    match dead_code.scope {
+    match &dead_code.scope {
         Scope::LambdaPattern(pattern, _) => {
             if pattern.pat_bind().is_some_and(|at| {
                 at.ident().expect("at.ident").syntax() == &dead_code.binding.decl_node
@@ -97,7 +236,8 @@ fn dead_to_edit(dead_code: DeadCode) -> Option<Edit> {
                         break;
                     } else if token.kind() == SyntaxKind::TOKEN_R_BRACE {
                         // last binding before `}`, find the trailing comma before
-                        let Some(mut prev) = dead_code.binding.decl_node.prev_sibling_or_token() else {
+                        let Some(mut prev) = dead_code.binding.decl_node.prev_sibling_or_token()
+                        else {
                             break;
                         };
                         while prev.kind() == SyntaxKind::TOKEN_WHITESPACE {
@@ -123,29 +263,60 @@ fn dead_to_edit(dead_code: DeadCode) -> Option<Edit> {
         }
 
         Scope::LetIn(let_in) => {
-            if let_in
-                .attrpath_values()
-                .any(|entry| *entry.syntax() == dead_code.binding.decl_node)
-            {
+            if let Some(node) = entry_to_remove(let_in, &dead_code.binding.decl_node) {
+                let range = node.text_range();
+                start = usize::from(range.start());
+                end = usize::from(range.end());
+                replace_node = node;
                 replacement = Some(String::new());
-            } else if let Some(ident) = let_in
-                .inherits()
-                .flat_map(|inherit| {
-                    inherit
-                        .attrs()
-                        .filter(|attr| attr.syntax() == &dead_code.binding.decl_node)
-                })
-                .next()
-            {
-                let range = ident.syntax().text_range();
+            }
+        }
+
+        Scope::LegacyLet(legacy_let) => {
+            if let Some(node) = entry_to_remove(legacy_let, &dead_code.binding.decl_node) {
+                let range = node.text_range();
+                start = usize::from(range.start());
+                end = usize::from(range.end());
+                replace_node = node;
+                replacement = Some(String::new());
+            }
+        }
+
+        Scope::LetAttrSet(attr_set) => {
+            if let Some(node) = entry_to_remove(attr_set, &dead_code.binding.decl_node) {
+                let range = node.text_range();
                 start = usize::from(range.start());
                 end = usize::from(range.end());
-                replace_node = ident.syntax().clone();
+                replace_node = node;
                 replacement = Some(String::new());
             }
         }
 
-        Scope::RecAttrSet(_) => {}
+        Scope::RecAttrSet(attr_set) => {
+            // remove the `rec` keyword and any whitespace after it
+            let rec = attr_set.rec_token()?;
+            let mut end = usize::from(rec.text_range().end());
+            if let Some(next) = rec.next_sibling_or_token() {
+                if next.kind() == SyntaxKind::TOKEN_WHITESPACE {
+                    end = usize::from(next.text_range().end());
+                }
+            }
+            return Some(Edit {
+                start: usize::from(rec.text_range().start()),
+                end,
+                replacement: String::new(),
+            });
+        }
+
+        Scope::With(with) => {
+            // remove the `with ...;` prefix, keeping whitespace before it
+            let body = with.body()?;
+            return Some(Edit {
+                start,
+                end: usize::from(body.syntax().text_range().start()),
+                replacement: String::new(),
+            });
+        }
     }
 
     replacement.map(|replacement| {
@@ -168,6 +339,26 @@ fn dead_to_edit(dead_code: DeadCode) -> Option<Edit> {
     })
 }
 
+/// The `key = value;` entry, or the name in an `inherit` clause that
+/// declares `decl_node`
+fn entry_to_remove(
+    entries: &impl HasEntry,
+    decl_node: &SyntaxNode<NixLanguage>,
+) -> Option<SyntaxNode<NixLanguage>> {
+    if entries
+        .attrpath_values()
+        .any(|entry| entry.syntax() == decl_node)
+    {
+        Some(decl_node.clone())
+    } else {
+        entries
+            .inherits()
+            .flat_map(|inherit| inherit.attrs())
+            .find(|attr| attr.syntax() == decl_node)
+            .map(|attr| attr.syntax().clone())
+    }
+}
+
 fn remove_empty_scopes(node: &SyntaxNode<NixLanguage>, edits: &mut Vec<Edit>) {
     match node.kind() {
         // remove empty `let in` constructs
@@ -15,6 +15,10 @@ macro_rules! no_edits {
         let s = $s.to_string();
         assert_eq!(run(&s, &Settings::default()), (s, false));
     };
+    ($s: expr, $settings: expr) => {
+        let s = $s.to_string();
+        assert_eq!(run(&s, &$settings), (s, false));
+    };
 }
 
 macro_rules! has_edits {
@@ -138,7 +142,8 @@ fn lambda_arg_anon() {
 
 #[test]
 fn lambda_at_pattern_dead() {
-    has_edits!("dead@{ dead2 ? dead, ... }: false", "{ ... }: false");
+    no_edits!("dead@{ dead2 ? dead, ... }: false");
+    has_edits!("dead@{ dead2 ? dead.x, ... }: false", "{ ... }: false");
 }
 
 #[test]
@@ -171,12 +176,17 @@ fn lambda_at_shadowed() {
 
 #[test]
 fn lambda_pattern_dead() {
-    has_edits!("alive@{ dead, ... }: alive", "alive@{ ... }: alive");
+    no_edits!("alive@{ dead, ... }: alive");
+    has_edits!("alive@{ dead, ... }: alive.x", "alive@{ ... }: alive.x");
 }
 
 #[test]
 fn lambda_pattern_default_dead() {
-    has_edits!("alive@{ dead ? true, ... }: alive", "alive@{ ... }: alive");
+    no_edits!("alive@{ dead ? true, ... }: alive");
+    has_edits!(
+        "alive@{ dead ? true, ... }: alive.x",
+        "alive@{ ... }: alive.x"
+    );
 }
 
 #[test]
@@ -210,3 +220,175 @@ fn attrset_no_trailing_comma() {
         "{ bar }: bar"
     );
 }
+
+#[test]
+fn skip_inherit_from_source() {
+    has_edits!(
+        "let\n  y = {};\n  inherit (y)\n    # deadnix: skip\n    a\n    b;\nin 1\n",
+        "let\n  y = {};\n  inherit (y)\n    # deadnix: skip\n    a;\nin 1\n"
+    );
+}
+
+#[test]
+fn no_underscore_inherit_from_source() {
+    has_edits!(
+        "let y = {}; inherit (y) _a b; in 1",
+        "let y = {}; inherit (y) _a; in 1",
+        Settings {
+            no_underscore: true,
+            ..Settings::default()
+        }
+    );
+}
+
+#[test]
+fn let_in_dead_cycle() {
+    has_edits!(
+        "let alive = 1; dead1 = dead2; dead2 = dead1; in alive",
+        "let alive = 1; in alive"
+    );
+}
+
+#[test]
+fn let_in_dead_cycle_only() {
+    has_edits!("let dead1 = dead2; dead2 = dead1; in alive", "alive");
+}
+
+fn warn_unused_with() -> Settings {
+    Settings {
+        warn_unused_with: true,
+        ..Settings::default()
+    }
+}
+
+#[test]
+fn with_alive() {
+    no_edits!("with pkgs; [ hello ]", warn_unused_with());
+}
+
+#[test]
+fn with_dead() {
+    has_edits!("{ x }: with lib; [ x ]", "{ x }: [ x ]", warn_unused_with());
+}
+
+#[test]
+fn with_dead_multiline() {
+    has_edits!(
+        "{\n  meta = with lib; {\n    x = 1;\n  };\n}",
+        "{\n  meta = {\n    x = 1;\n  };\n}",
+        warn_unused_with()
+    );
+}
+
+#[test]
+fn with_dead_nested() {
+    has_edits!(
+        "{ pkgs }: with lib; with pkgs; 23",
+        "{ pkgs }: 23",
+        warn_unused_with()
+    );
+}
+
+#[test]
+fn with_dead_let_inside() {
+    has_edits!("with lib; let dead = 1; in 23", "23", warn_unused_with());
+}
+
+#[test]
+fn rec_attrset_needed() {
+    let settings = Settings {
+        warn_unused_rec: true,
+        ..Settings::default()
+    };
+    let s = "rec { a = 1; b = a; }".to_string();
+    assert_eq!(run(&s, &settings), (s, false));
+}
+
+#[test]
+fn rec_attrset_unused() {
+    has_edits!(
+        "mkDerivation rec { pname = \"x\"; }",
+        "mkDerivation { pname = \"x\"; }",
+        Settings {
+            warn_unused_rec: true,
+            ..Settings::default()
+        }
+    );
+}
+
+#[test]
+fn rec_attrset_unused_no_whitespace() {
+    has_edits!(
+        "rec{ a = 1; }",
+        "{ a = 1; }",
+        Settings {
+            warn_unused_rec: true,
+            ..Settings::default()
+        }
+    );
+}
+
+#[test]
+fn let_attrs_dead() {
+    has_edits!(
+        "let cfg = { alive = 1; dead = 2; inherit dead2; }; in cfg.alive",
+        "let cfg = { alive = 1; }; in cfg.alive",
+        Settings {
+            warn_unused_attrs: true,
+            ..Settings::default()
+        }
+    );
+}
+
+#[test]
+fn let_string_dead() {
+    has_edits!(
+        "let \"dead\" = 1; alive = 2; in alive",
+        "let alive = 2; in alive"
+    );
+}
+
+#[test]
+fn let_inherit_string_dead() {
+    has_edits!(
+        "let inherit (x) \"dead\" alive; in alive",
+        "let inherit (x) alive; in alive"
+    );
+}
+
+#[test]
+fn shadowing_no_edits() {
+    let settings = Settings {
+        warn_shadowing: true,
+        ..Settings::default()
+    };
+    let s = "{ pkgs }: map (pkgs: pkgs.hello) pkgs".to_string();
+    assert_eq!(run(&s, &settings), (s, false));
+}
+
+#[test]
+fn legacy_let_dead() {
+    has_edits!(
+        "let { alive = 1; dead = 2; body = alive; }",
+        "let { alive = 1; body = alive; }"
+    );
+}
+
+#[test]
+fn legacy_let_inherit_dead() {
+    has_edits!(
+        "let { inherit alive dead; body = alive; }",
+        "let { inherit alive; body = alive; }"
+    );
+}
+
+#[test]
+fn used_underscore_kept() {
+    no_edits!(
+        "let _a = 1; in _a",
+        Settings {
+            warn_used_underscore: true,
+            ..Settings::default()
+        }
+    );
+}
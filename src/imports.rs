@@ -0,0 +1,125 @@
+use crate::{
+    dead_code::Settings,
+    resolve::{strip_parens, Index},
+    scope::Scope,
+};
+use rnix::{
+    ast::{Expr, Param, Pattern, Root},
+    NixLanguage,
+};
+use rowan::{api::SyntaxNode, ast::AstNode};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Pattern names consumed by the top-level lambdas of scanned files
+///
+/// Resolves `{ ... }@args: import ./file.nix args` so that only the
+/// pattern names which `file.nix` actually uses are considered used
+/// through `args`, see [`Settings::find_dead_code_with_imports()`].
+#[derive(Debug, Clone, Default)]
+pub struct Imports {
+    /// By canonical path, `None` if the file does not evaluate to a
+    /// lambda with an attrset pattern
+    files: HashMap<PathBuf, Option<HashSet<String>>>,
+}
+
+impl Imports {
+    /// An empty set of files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Analyze the top-level lambda of the file at `path` which has
+    /// been parsed into `node`
+    ///
+    /// The pattern names are checked regardless of the [`Settings`]
+    /// that the files are scanned with, so that only those which the
+    /// file actually uses are consumed.
+    pub fn add(&mut self, path: &Path, node: &SyntaxNode<NixLanguage>) {
+        let consumed = top_pattern(node).map(|pattern| {
+            let dead = Settings::default()
+                .find_dead_code(node)
+                .into_iter()
+                .filter(|dead_code| {
+                    dead_code.unused
+                        && match &dead_code.scope {
+                            Scope::LambdaPattern(dead_pattern, _) => dead_pattern == &pattern,
+                            _ => false,
+                        }
+                })
+                .map(|dead_code| dead_code.binding.name.as_str().to_string())
+                .collect::<HashSet<_>>();
+            pattern
+                .pat_entries()
+                .filter_map(|entry| entry.ident())
+                .map(|ident| ident.syntax().text().to_string())
+                .filter(|name| !dead.contains(name))
+                .collect()
+        });
+        self.files.insert(canonical(path), consumed);
+    }
+
+    /// Read and add the files that the file at `path`, which has been
+    /// parsed into `node`, passes `args` to with `import ./file.nix args`
+    ///
+    /// Files that cannot be read or parsed are skipped.
+    pub fn add_imported(&mut self, path: &Path, node: &SyntaxNode<NixLanguage>) {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for import_path in Index::new(node).import_paths() {
+            let Some(file) = import_file(dir, import_path) else {
+                continue;
+            };
+            if let Ok(content) = fs::read_to_string(&file) {
+                let ast = Root::parse(&content);
+                if ast.errors().is_empty() {
+                    self.add(&file, &ast.syntax());
+                }
+            }
+        }
+    }
+
+    /// The pattern names consumed by the file that is imported with
+    /// the path literal `import_path` from a file in `dir`
+    ///
+    /// `None` if that file has not been added, or does not evaluate
+    /// to a lambda with an attrset pattern.
+    pub(crate) fn consumed(&self, dir: &Path, import_path: &str) -> Option<&HashSet<String>> {
+        let path = import_file(dir, import_path)?;
+        self.files.get(&canonical(&path))?.as_ref()
+    }
+}
+
+/// The file of the relative path literal `import_path` in a file in
+/// `dir`
+fn import_file(dir: &Path, import_path: &str) -> Option<PathBuf> {
+    if !import_path.starts_with("./") && !import_path.starts_with("../") {
+        return None;
+    }
+    let mut path = dir.join(import_path);
+    if path.is_dir() {
+        path.push("default.nix");
+    }
+    Some(path)
+}
+
+/// The `{ ... }` of the lambda that the file evaluates to
+fn top_pattern(node: &SyntaxNode<NixLanguage>) -> Option<Pattern> {
+    let expr = match Root::cast(node.clone()) {
+        Some(root) => root.expr()?,
+        None => Expr::cast(node.clone())?,
+    };
+    match strip_parens(expr) {
+        Expr::Lambda(lambda) => match lambda.param()? {
+            Param::Pattern(pattern) => Some(pattern),
+            Param::IdentParam(_) => None,
+        },
+        _ => None,
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
@@ -13,8 +13,13 @@
 //! let results = deadnix::Settings {
 //!     no_lambda_arg: false,
 //!     no_lambda_pattern_names: false,
+//!     no_top_lambda_pattern_names: false,
 //!     no_underscore: false,
 //!     warn_used_underscore: false,
+//!     warn_unused_with: false,
+//!     warn_unused_rec: false,
+//!     warn_unused_attrs: false,
+//!     warn_shadowing: false,
 //! }.find_dead_code(&ast.syntax());
 //!
 //! for dead_code in &results {
@@ -24,16 +29,36 @@
 
 #![deny(unsafe_code, missing_docs, bare_trait_objects)]
 
+#[cfg(feature = "json-out")]
+pub mod apply;
+mod apply_tests;
 mod binding;
 mod dead_code;
 mod dead_code_tests;
+mod diff;
+mod diff_tests;
 mod edit;
 mod edit_tests;
+mod imports;
+mod lines;
+#[cfg(feature = "json-out")]
+pub mod lsp;
+mod lsp_tests;
 pub mod report;
+mod report_tests;
+mod resolve;
+#[cfg(feature = "json-out")]
+mod schema;
 mod scope;
-mod usage;
+mod session;
+mod session_tests;
 
-pub use binding::Binding;
+pub use binding::{Binding, Name};
 pub use dead_code::{DeadCode, Settings};
+pub use diff::diff_dead_code;
 pub use edit::edit_dead_code;
+pub use imports::Imports;
+#[cfg(feature = "json-out")]
+pub use schema::JSON_SCHEMA_VERSION;
 pub use scope::Scope;
+pub use session::Session;
@@ -0,0 +1,127 @@
+use rnix::{TextRange, TextSize};
+
+/// How columns are counted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnUnit {
+    /// Bytes of UTF-8
+    Bytes,
+    /// Unicode scalar values
+    Chars,
+    /// Code units of UTF-16, as in the Language Server Protocol
+    Utf16,
+}
+
+/// Line starts of a file, for line/column positions of byte offsets
+pub(crate) struct Lines<'a> {
+    content: &'a str,
+    offsets: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(content: &'a str) -> Self {
+        let mut offset = 0;
+        let mut offsets = vec![offset];
+        while let Some(next) = content[offset..].find('\n') {
+            offset += next + 1;
+            offsets.push(offset);
+        }
+        Lines { content, offsets }
+    }
+
+    /// 1-based line number and the byte offset of its start
+    pub(crate) fn line(&self, offset: usize) -> (usize, usize) {
+        let line_number = self.offsets.partition_point(|&start| start <= offset);
+        (line_number, self.offsets[line_number - 1])
+    }
+
+    /// Number of lines, not counting an empty one after a final line
+    /// break
+    pub(crate) fn line_count(&self) -> usize {
+        if self.offsets.len() > 1 && self.content.ends_with('\n') {
+            self.offsets.len() - 1
+        } else {
+            self.offsets.len()
+        }
+    }
+
+    /// The whole text
+    #[cfg(feature = "json-out")]
+    pub(crate) fn content(&self) -> &'a str {
+        self.content
+    }
+
+    /// Byte offset of the start of the 1-based `line_number`
+    pub(crate) fn line_start(&self, line_number: usize) -> usize {
+        self.offsets[line_number - 1]
+    }
+
+    /// Text of the 1-based `line_number`, without the line break
+    pub(crate) fn text(&self, line_number: usize) -> &'a str {
+        let start = self.line_start(line_number);
+        let end = self
+            .offsets
+            .get(line_number)
+            .map_or(self.content.len(), |end| end - 1);
+        &self.content[start..end]
+    }
+
+    /// 1-based line number of the start of `range`, with 1-based
+    /// start and end columns in bytes
+    pub(crate) fn columns(&self, range: TextRange) -> (usize, usize, usize) {
+        let start = usize::from(range.start());
+        let (line_number, line_offset) = self.line(start);
+        (
+            line_number,
+            start - line_offset + 1,
+            usize::from(range.end()) - line_offset + 1,
+        )
+    }
+
+    /// 1-based line number and 1-based column in chars
+    pub(crate) fn position(&self, offset: TextSize) -> (usize, usize) {
+        self.position_in(offset, ColumnUnit::Chars)
+    }
+
+    /// 1-based line number and 1-based column in `unit`
+    pub(crate) fn position_in(&self, offset: TextSize, unit: ColumnUnit) -> (usize, usize) {
+        let offset = usize::from(offset);
+        let (line_number, line_offset) = self.line(offset);
+        let before = &self.content[line_offset..offset];
+        let column = match unit {
+            ColumnUnit::Bytes => before.len(),
+            ColumnUnit::Chars => before.chars().count(),
+            ColumnUnit::Utf16 => before.encode_utf16().count(),
+        };
+        (line_number, column + 1)
+    }
+
+    /// Byte offset of the 1-based `column` in `unit` of the 1-based
+    /// `line_number`
+    ///
+    /// Columns beyond the end of a line are clamped to it, and lines
+    /// beyond the end of the content to its end.
+    #[cfg(feature = "json-out")]
+    pub(crate) fn offset_in(&self, line_number: usize, column: usize, unit: ColumnUnit) -> usize {
+        let Some(&line_start) = line_number
+            .checked_sub(1)
+            .and_then(|index| self.offsets.get(index))
+        else {
+            return self.content.len();
+        };
+
+        let mut offset = line_start;
+        let mut units = 1;
+        for c in self.content[line_start..].chars() {
+            if units >= column || c == '\n' {
+                break;
+            }
+            units += match unit {
+                ColumnUnit::Bytes => c.len_utf8(),
+                ColumnUnit::Chars => 1,
+                ColumnUnit::Utf16 => c.len_utf16(),
+            };
+            offset += c.len_utf8();
+        }
+        offset
+    }
+}
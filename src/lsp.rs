@@ -0,0 +1,407 @@
+//! Language server over stdio
+//!
+//! Publishes [`DeadCode`] as diagnostics whenever a document is opened
+//! or changed, and offers code actions that remove the dead code with
+//! [`edit_dead_code()`] or skip it with a `# deadnix: skip` pragma.
+//!
+//! [`Settings`] are taken from the `initializationOptions` of the
+//! client, with camelCase names like `{ "noLambdaArg": true }`.
+
+use crate::{
+    dead_code::DeadCode,
+    edit::{dead_to_edit, edit_dead_code},
+    lines::{ColumnUnit, Lines},
+    scope::Scope,
+    Settings,
+};
+use rnix::{TextRange, TextSize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// `DiagnosticSeverity.Warning`
+const SEVERITY_WARNING: u8 = 2;
+/// `DiagnosticTag.Unnecessary`
+const TAG_UNNECESSARY: u8 = 1;
+/// `TextDocumentSyncKind.Full`
+const SYNC_FULL: u8 = 1;
+
+/// Run the language server until the client sends `exit` or closes
+/// `input`
+///
+/// # Errors
+///
+/// Fails on I/O errors and on messages without a valid
+/// `Content-Length` header.
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server = Server {
+        output,
+        settings: None,
+        documents: HashMap::new(),
+    };
+    while let Some(message) = read_message(&mut input)? {
+        match serde_json::from_str::<Value>(&message) {
+            Ok(message) => {
+                if !server.handle(&message)? {
+                    break;
+                }
+            }
+            Err(error) => server.respond_error(&Value::Null, PARSE_ERROR, &error.to_string())?,
+        }
+    }
+    Ok(())
+}
+
+/// Read one message with its `Content-Length` header
+///
+/// `None` at the end of `input`.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+struct Server<W> {
+    output: W,
+    /// `None` until `initialize`
+    settings: Option<Settings>,
+    /// Open documents by URI
+    documents: HashMap<String, String>,
+}
+
+impl<W: Write> Server<W> {
+    /// Handle one message, `false` on `exit`
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let id = message.get("id");
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // a request without method, as opposed to a response
+            if let (Some(id), None, None) = (id, message.get("result"), message.get("error")) {
+                self.respond_error(id, INVALID_REQUEST, "missing method")?;
+            }
+            return Ok(true);
+        };
+        let params = message.get("params").unwrap_or(&Value::Null);
+
+        if method == "exit" {
+            return Ok(false);
+        }
+        if method == "initialize" {
+            let settings = match params.get("initializationOptions") {
+                None | Some(Value::Null) => Ok(Settings::default()),
+                Some(options) => serde_json::from_value(options.clone()),
+            };
+            let id = id.unwrap_or(&Value::Null);
+            return match settings {
+                Ok(settings) => {
+                    self.settings = Some(settings);
+                    self.respond(id, &initialize_result())?;
+                    Ok(true)
+                }
+                Err(error) => {
+                    self.respond_error(id, INVALID_PARAMS, &error.to_string())?;
+                    Ok(true)
+                }
+            };
+        }
+        let Some(settings) = self.settings.clone() else {
+            if let Some(id) = id {
+                self.respond_error(id, SERVER_NOT_INITIALIZED, "server not initialized")?;
+            }
+            return Ok(true);
+        };
+
+        match (method, id) {
+            ("shutdown", Some(id)) => self.respond(id, &Value::Null)?,
+
+            ("textDocument/didOpen", None) => {
+                let document = &params["textDocument"];
+                if let (Some(uri), Some(text)) =
+                    (document["uri"].as_str(), document["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    self.publish_diagnostics(&settings, uri)?;
+                }
+            }
+
+            ("textDocument/didChange", None) => {
+                let uri = params["textDocument"]["uri"].as_str();
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    self.publish_diagnostics(&settings, uri)?;
+                }
+            }
+
+            ("textDocument/didClose", None) => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    self.documents.remove(uri);
+                    self.notify(
+                        "textDocument/publishDiagnostics",
+                        &json!({ "uri": uri, "diagnostics": [] }),
+                    )?;
+                }
+            }
+
+            ("textDocument/codeAction", Some(id)) => {
+                let actions = params["textDocument"]["uri"].as_str().and_then(|uri| {
+                    let text = self.documents.get(uri)?;
+                    Some(code_actions(&settings, uri, text, &params["range"]))
+                });
+                match actions {
+                    Some(actions) => self.respond(id, &actions)?,
+                    None => self.respond_error(id, INVALID_PARAMS, "unknown document")?,
+                }
+            }
+
+            (_, Some(id)) => {
+                self.respond_error(id, METHOD_NOT_FOUND, &format!("unknown method {method}"))?;
+            }
+
+            // ignore other notifications
+            (_, None) => {}
+        }
+        Ok(true)
+    }
+
+    fn publish_diagnostics(&mut self, settings: &Settings, uri: &str) -> io::Result<()> {
+        let text = &self.documents[uri];
+        let lines = Lines::new(text);
+        let diagnostics = find_dead_code(settings, text)
+            .iter()
+            .map(|result| diagnostic(&lines, uri, result))
+            .collect::<Vec<_>>();
+        self.notify(
+            "textDocument/publishDiagnostics",
+            &json!({ "uri": uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    fn respond(&mut self, id: &Value, result: &Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    fn respond_error(&mut self, id: &Value, code: i64, message: &str) -> io::Result<()> {
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": {
+                "openClose": true,
+                "change": SYNC_FULL,
+            },
+            "codeActionProvider": {
+                "codeActionKinds": ["quickfix"],
+            },
+        },
+        "serverInfo": {
+            "name": "deadnix",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// Documents with syntax errors yield no results until they are fixed
+fn find_dead_code(settings: &Settings, text: &str) -> Vec<DeadCode> {
+    let ast = rnix::Root::parse(text);
+    if ast.errors().is_empty() {
+        settings.find_dead_code(&ast.syntax())
+    } else {
+        Vec::new()
+    }
+}
+
+fn diagnostic(lines: &Lines, uri: &str, result: &DeadCode) -> Value {
+    let mut diagnostic = json!({
+        "range": range(lines, result.binding.name.text_range()),
+        "severity": SEVERITY_WARNING,
+        "source": "deadnix",
+        "message": result.to_string(),
+    });
+    if let Some((scope, binding)) = &result.shadowed {
+        diagnostic["relatedInformation"] = json!([{
+            "location": {
+                "uri": uri,
+                "range": range(lines, binding.name.text_range()),
+            },
+            "message": format!("Shadowed {scope}: {}", binding.name),
+        }]);
+    } else {
+        diagnostic["tags"] = json!([TAG_UNNECESSARY]);
+    }
+    diagnostic
+}
+
+/// Quick fixes for the results that overlap `range`
+fn code_actions(settings: &Settings, uri: &str, text: &str, range: &Value) -> Value {
+    let lines = Lines::new(text);
+    let (Some(start), Some(end)) = (
+        offset(&lines, &range["start"]),
+        offset(&lines, &range["end"]),
+    ) else {
+        return json!([]);
+    };
+
+    let mut actions = Vec::new();
+    for result in find_dead_code(settings, text) {
+        let name_range = result.binding.name.text_range();
+        if usize::from(name_range.start()) > end || usize::from(name_range.end()) < start {
+            continue;
+        }
+        let diagnostic = diagnostic(&lines, uri, &result);
+
+        let title = match &result.scope {
+            Scope::LambdaArg(_, _) => {
+                format!(
+                    "Prefix unused lambda argument with `_`: {}",
+                    result.binding.name
+                )
+            }
+            Scope::RecAttrSet(_) => "Remove superfluous `rec`".to_string(),
+            Scope::With(_) => format!("Remove unused `with {};`", result.binding.name),
+            _ => format!(
+                "Remove {}: {}",
+                result.rule_id().replace('-', " "),
+                result.binding.name
+            ),
+        };
+        let skip_edit = skip_edit(&lines, &result);
+        // shadowing and used `_` bindings have nothing to remove
+        if dead_to_edit(&result).is_some() {
+            let (edited, _) = edit_dead_code(text, Some(result).into_iter());
+            actions.push(json!({
+                "title": title,
+                "kind": "quickfix",
+                "diagnostics": [diagnostic],
+                "isPreferred": true,
+                "edit": { "changes": { uri: [text_edit(&lines, &edited)] } },
+            }));
+        }
+
+        actions.push(json!({
+            "title": "Skip with `# deadnix: skip`",
+            "kind": "quickfix",
+            "diagnostics": [diagnostic],
+            "edit": { "changes": { uri: [skip_edit] } },
+        }));
+    }
+    Value::Array(actions)
+}
+
+/// Insert a `# deadnix: skip` line above the declaration of `result`,
+/// with the same indentation
+fn skip_edit(lines: &Lines, result: &DeadCode) -> Value {
+    let offset = usize::from(result.binding.decl_node.text_range().start());
+    let (_, line_start) = lines.line(offset);
+    let indentation = lines.content()[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect::<String>();
+    let position = position(lines, line_start);
+    json!({
+        "range": { "start": position, "end": position },
+        "newText": format!("{indentation}# deadnix: skip\n"),
+    })
+}
+
+/// The smallest `TextEdit` that turns the document into `edited`
+fn text_edit(lines: &Lines, edited: &str) -> Value {
+    let original = lines.content();
+    let mut prefix = original
+        .bytes()
+        .zip(edited.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = original[prefix..]
+        .bytes()
+        .rev()
+        .zip(edited[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(original.len() - suffix) {
+        suffix -= 1;
+    }
+
+    json!({
+        "range": {
+            "start": position(lines, prefix),
+            "end": position(lines, original.len() - suffix),
+        },
+        "newText": &edited[prefix..edited.len() - suffix],
+    })
+}
+
+/// LSP position of a byte `offset`, which counts UTF-16 code units
+fn position(lines: &Lines, offset: usize) -> Value {
+    let offset = TextSize::try_from(offset).expect("TextSize");
+    let (line, character) = lines.position_in(offset, ColumnUnit::Utf16);
+    json!({ "line": line - 1, "character": character - 1 })
+}
+
+fn range(lines: &Lines, range: TextRange) -> Value {
+    json!({
+        "start": position(lines, range.start().into()),
+        "end": position(lines, range.end().into()),
+    })
+}
+
+/// Byte offset of an LSP `position`
+///
+/// Positions beyond the end of a line are clamped to it.
+fn offset(lines: &Lines, position: &Value) -> Option<usize> {
+    let line = usize::try_from(position["line"].as_u64()?).ok()?;
+    let character = usize::try_from(position["character"].as_u64()?).ok()?;
+    Some(lines.offset_in(line + 1, character + 1, ColumnUnit::Utf16))
+}
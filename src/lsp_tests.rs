@@ -0,0 +1,298 @@
+#![cfg(all(test, feature = "json-out"))]
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read};
+
+const URI: &str = "file:///test.nix";
+
+/// Run a scripted session, returning all messages of the server
+fn session(messages: &[Value]) -> Vec<Value> {
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes());
+    }
+    let mut output = Vec::new();
+    crate::lsp::serve(&input[..], &mut output).unwrap();
+
+    let mut output = BufReader::new(&output[..]);
+    let mut results = Vec::new();
+    loop {
+        let mut header = String::new();
+        if output.read_line(&mut header).unwrap() == 0 {
+            break;
+        }
+        let length = header
+            .trim_end()
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut empty = String::new();
+        output.read_line(&mut empty).unwrap();
+        assert_eq!(empty, "\r\n");
+        let mut body = vec![0; length];
+        output.read_exact(&mut body).unwrap();
+        results.push(serde_json::from_slice(&body).unwrap());
+    }
+    results
+}
+
+fn initialize(options: &Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {}, "initializationOptions": options } })
+}
+
+fn did_open(text: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": URI, "languageId": "nix", "version": 1, "text": text } } })
+}
+
+fn code_action(id: u64, start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": "textDocument/codeAction", "params": {
+        "textDocument": { "uri": URI },
+        "range": { "start": { "line": start.0, "character": start.1 }, "end": { "line": end.0, "character": end.1 } },
+        "context": { "diagnostics": [] },
+    } })
+}
+
+fn exit() -> Value {
+    json!({ "jsonrpc": "2.0", "method": "exit" })
+}
+
+#[test]
+fn lsp_initialize_shutdown() {
+    let results = session(&[
+        initialize(&Value::Null),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        exit(),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+    ]);
+    assert_eq!(2, results.len());
+    assert_eq!(results[0]["id"], 1);
+    assert_eq!(
+        results[0]["result"]["capabilities"]["textDocumentSync"]["change"],
+        1
+    );
+    assert_eq!(results[0]["result"]["serverInfo"]["name"], "deadnix");
+    assert_eq!(
+        results[1],
+        json!({ "jsonrpc": "2.0", "id": 2, "result": null })
+    );
+}
+
+#[test]
+fn lsp_not_initialized() {
+    let results = session(&[json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" })]);
+    assert_eq!(1, results.len());
+    assert_eq!(results[0]["error"]["code"], -32002);
+}
+
+#[test]
+fn lsp_unknown_method() {
+    let results = session(&[
+        initialize(&Value::Null),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "$/unknownNotification", "params": {} }),
+    ]);
+    assert_eq!(2, results.len());
+    assert_eq!(results[1]["id"], 2);
+    assert_eq!(results[1]["error"]["code"], -32601);
+}
+
+#[test]
+fn lsp_parse_error() {
+    let body = "{ not json";
+    let input = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    let mut output = Vec::new();
+    crate::lsp::serve(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("-32700"));
+}
+
+#[test]
+fn lsp_diagnostics_on_open() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("let\n  alive = 1;\n  dead = 2;\nin alive"),
+    ]);
+    assert_eq!(2, results.len());
+    assert_eq!(results[1]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(results[1]["params"]["uri"], URI);
+    let diagnostics = results[1]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(diagnostics[0]["message"], "Unused let binding: dead");
+    assert_eq!(diagnostics[0]["source"], "deadnix");
+    assert_eq!(diagnostics[0]["severity"], 2);
+    assert_eq!(diagnostics[0]["tags"], json!([1]));
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({
+            "start": { "line": 2, "character": 2 },
+            "end": { "line": 2, "character": 6 },
+        })
+    );
+}
+
+#[test]
+fn lsp_diagnostics_utf16() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("let s = \"😀\"; dead = 2; in s"),
+    ]);
+    let diagnostics = results[1]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 14 })
+    );
+}
+
+#[test]
+fn lsp_diagnostics_on_change_and_close() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("let dead = 1; in 2"),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "let alive = 1; in alive" }],
+        } }),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": URI, "version": 3 },
+            "contentChanges": [{ "text": "let alive = 1; in (alive" }],
+        } }),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didClose", "params": { "textDocument": { "uri": URI } } }),
+    ]);
+    assert_eq!(5, results.len());
+    assert_eq!(
+        results[1]["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(results[2]["params"]["diagnostics"], json!([]));
+    // syntax errors
+    assert_eq!(results[3]["params"]["diagnostics"], json!([]));
+    assert_eq!(results[4]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn lsp_initialization_options() {
+    let results = session(&[
+        initialize(&json!({ "noLambdaArg": true, "warnShadowing": true })),
+        did_open("x: y: let x = y; in x"),
+    ]);
+    let diagnostics = results[1]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(diagnostics[0]["message"], "Shadowing let binding: x");
+    assert!(diagnostics[0].get("tags").is_none());
+    assert_eq!(
+        diagnostics[0]["relatedInformation"][0]["message"],
+        "Shadowed lambda argument: x"
+    );
+}
+
+#[test]
+fn lsp_invalid_initialization_options() {
+    let results = session(&[initialize(&json!({ "noLambdaArg": "yes" }))]);
+    assert_eq!(results[0]["error"]["code"], -32602);
+}
+
+#[test]
+fn lsp_code_action_remove() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("let\n  alive = 1;\n  dead = 2;\nin alive"),
+        code_action(2, (2, 3), (2, 3)),
+    ]);
+    assert_eq!(3, results.len());
+    assert_eq!(results[2]["id"], 2);
+    let actions = results[2]["result"].as_array().unwrap();
+    assert_eq!(2, actions.len());
+
+    assert_eq!(actions[0]["title"], "Remove unused let binding: dead");
+    assert_eq!(actions[0]["kind"], "quickfix");
+    assert_eq!(actions[0]["isPreferred"], true);
+    assert_eq!(
+        actions[0]["diagnostics"][0]["message"],
+        "Unused let binding: dead"
+    );
+    assert_eq!(
+        actions[0]["edit"]["changes"][URI],
+        json!([{
+            "range": { "start": { "line": 2, "character": 0 }, "end": { "line": 3, "character": 0 } },
+            "newText": "",
+        }])
+    );
+
+    assert_eq!(
+        actions[1]["edit"]["changes"][URI],
+        json!([{
+            "range": { "start": { "line": 2, "character": 0 }, "end": { "line": 2, "character": 0 } },
+            "newText": "  # deadnix: skip\n",
+        }])
+    );
+}
+
+#[test]
+fn lsp_code_action_lambda_arg() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("dead: 1"),
+        code_action(2, (0, 0), (0, 7)),
+    ]);
+    let actions = results[2]["result"].as_array().unwrap();
+    assert_eq!(2, actions.len());
+    assert_eq!(
+        actions[0]["title"],
+        "Prefix unused lambda argument with `_`: dead"
+    );
+    assert_eq!(
+        actions[0]["edit"]["changes"][URI],
+        json!([{
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+            "newText": "_",
+        }])
+    );
+}
+
+#[test]
+fn lsp_code_action_outside() {
+    let results = session(&[
+        initialize(&Value::Null),
+        did_open("let\n  alive = 1;\n  dead = 2;\nin alive"),
+        code_action(2, (1, 2), (1, 4)),
+    ]);
+    assert_eq!(results[2]["result"], json!([]));
+}
+
+#[test]
+fn lsp_code_action_shadowing_skip_only() {
+    let results = session(&[
+        initialize(&json!({ "warnShadowing": true })),
+        did_open("x: map (x: x) x"),
+        code_action(2, (0, 8), (0, 8)),
+    ]);
+    let actions = results[2]["result"].as_array().unwrap();
+    assert_eq!(1, actions.len());
+    assert_eq!(actions[0]["title"], "Skip with `# deadnix: skip`");
+}
+
+#[test]
+fn lsp_code_action_shadowing_empty_let() {
+    // the edit pass would remove the unrelated `let in`
+    let results = session(&[
+        initialize(&json!({ "warnShadowing": true })),
+        did_open("x: let in let x = 1; in x"),
+        code_action(2, (0, 14), (0, 14)),
+    ]);
+    let actions = results[2]["result"].as_array().unwrap();
+    assert_eq!(1, actions.len());
+    assert_eq!(actions[0]["title"], "Skip with `# deadnix: skip`");
+}
+
+#[test]
+fn lsp_code_action_unknown_document() {
+    let results = session(&[initialize(&Value::Null), code_action(2, (0, 0), (0, 0))]);
+    assert_eq!(results[1]["error"]["code"], -32602);
+}
@@ -1,22 +1,23 @@
 use clap::{Arg, ArgAction, Command};
+use deadnix::{diff_dead_code, edit_dead_code, report, Imports, Settings};
 #[cfg(feature = "json-out")]
 use serde_json::json;
 use std::{collections::HashSet, fs, path::Path};
 
-mod binding;
-mod dead_code;
-mod dead_code_tests;
-mod edit;
-mod edit_tests;
-mod report;
-mod scope;
-mod usage;
-
 #[derive(Clone, Copy, Debug)]
 enum OutputFormat {
     HumanReadable,
     #[cfg(feature = "json-out")]
     Json,
+    #[cfg(feature = "json-out")]
+    Sarif,
+    Checkstyle,
+    Github,
+    #[cfg(feature = "json-out")]
+    CodeClimate,
+    Junit,
+    Html,
+    Short,
 }
 
 fn main() {
@@ -24,6 +25,7 @@ fn main() {
         .version(env!("CARGO_PKG_VERSION"))
         .author("Astro <astro@spaceboyz.net>")
         .about("Find dead code in .nix files")
+        .args_conflicts_with_subcommands(true)
         .arg(
             Arg::new("NO_LAMBDA_ARG")
                 .action(ArgAction::SetTrue)
@@ -38,6 +40,13 @@ fn main() {
                 .long("no-lambda-pattern-names")
                 .help("Don't check lambda attrset pattern names (don't break nixpkgs callPackage)"),
         )
+        .arg(
+            Arg::new("NO_TOP_LAMBDA_PATTERN_NAMES")
+                .action(ArgAction::SetTrue)
+                .short('T')
+                .long("no-top-lambda-pattern-names")
+                .help("Don't check the attrset pattern names of the file's top-level lambda only"),
+        )
         .arg(
             Arg::new("NO_UNDERSCORE")
                 .action(ArgAction::SetTrue)
@@ -55,6 +64,41 @@ fn main() {
                 .long("warn-used-underscore")
                 .help("Warn if bindings are referenced that start with '_'"),
         )
+        .arg(
+            Arg::new("WARN_UNUSED_WITH")
+                .action(ArgAction::SetTrue)
+                .short('w')
+                .long("warn-unused-with")
+                .help("Warn if with expressions provide no variables"),
+        )
+        .arg(
+            Arg::new("WARN_UNUSED_REC")
+                .action(ArgAction::SetTrue)
+                .short('R')
+                .long("warn-unused-rec")
+                .help("Warn if rec attrsets do not reference their own bindings"),
+        )
+        .arg(
+            Arg::new("WARN_UNUSED_ATTRS")
+                .action(ArgAction::SetTrue)
+                .short('A')
+                .long("warn-unused-attrs")
+                .help("Warn if attributes of let-bound attrsets are never selected"),
+        )
+        .arg(
+            Arg::new("WARN_SHADOWING")
+                .action(ArgAction::SetTrue)
+                .short('S')
+                .long("warn-shadowing")
+                .help("Warn if bindings shadow a binding of an enclosing scope"),
+        )
+        .arg(
+            Arg::new("FOLLOW_IMPORTS")
+                .action(ArgAction::SetTrue)
+                .short('i')
+                .long("follow-imports")
+                .help("Check which pattern names @args passed to import ./file.nix are used by the scanned file"),
+        )
         .arg(
             Arg::new("QUIET")
                 .action(ArgAction::SetTrue)
@@ -69,6 +113,13 @@ fn main() {
                 .long("edit")
                 .help("Remove unused code and write to source file"),
         )
+        .arg(
+            Arg::new("DIFF")
+                .action(ArgAction::SetTrue)
+                .short('d')
+                .long("diff")
+                .help("Print a unified diff of --edit instead of writing files, exit with 1 if there are changes"),
+        )
         .arg(
             Arg::new("HIDDEN")
                 .action(ArgAction::SetTrue)
@@ -95,10 +146,27 @@ fn main() {
             Arg::new("OUTPUT_FORMAT")
                 .short('o')
                 .long("output-format")
-                .value_parser(["human-readable", "json"])
+                .value_parser([
+                    "human-readable",
+                    "json",
+                    "sarif",
+                    "checkstyle",
+                    "github",
+                    "codeclimate",
+                    "junit",
+                    "html",
+                    "short",
+                ])
                 .default_value("human-readable")
                 .help("Output format to use"),
         )
+        .arg(
+            Arg::new("COLUMNS")
+                .long("columns")
+                .value_parser(["bytes", "chars", "utf16"])
+                .default_value("bytes")
+                .help("Count columns of the short output format in bytes, chars or UTF-16 code units"),
+        )
         .arg(
             Arg::new("EXCLUDES")
                 .long("exclude")
@@ -111,19 +179,60 @@ fn main() {
                 .default_value(".")
                 .help(".nix files, or directories with .nix files inside"),
         )
+        .disable_help_subcommand(true)
+        .subcommand(Command::new("lsp").about("Run a language server on stdin/stdout"))
+        .subcommand(
+            Command::new("apply")
+                .about("Remove the dead code of a reviewed findings file of --output-format json")
+                .arg(
+                    Arg::new("FINDINGS")
+                        .required(true)
+                        .help("Output of --output-format json with the findings to apply"),
+                ),
+        )
         .get_matches();
 
+    if matches.subcommand_matches("lsp").is_some() {
+        #[cfg(feature = "json-out")]
+        {
+            let stdin = std::io::stdin();
+            deadnix::lsp::serve(stdin.lock(), std::io::stdout().lock()).expect("lsp");
+            return;
+        }
+        #[cfg(not(feature = "json-out"))]
+        panic!("`deadnix` needs to be built with `json-out` feature flag for the language server.");
+    }
+
+    if let Some(matches) = matches.subcommand_matches("apply") {
+        let path = matches.get_one::<String>("FINDINGS").expect("FINDINGS");
+        #[cfg(feature = "json-out")]
+        {
+            let success = apply(path);
+            std::process::exit(i32::from(!success));
+        }
+        #[cfg(not(feature = "json-out"))]
+        panic!("`deadnix` needs to be built with `json-out` feature flag to apply {path}.");
+    }
+
     let fail_on_reports = matches.get_flag("FAIL_ON_REPORTS");
     let mut report_count = 0;
 
-    let settings = dead_code::Settings {
+    let settings = Settings {
         no_lambda_arg: matches.get_flag("NO_LAMBDA_ARG"),
         no_lambda_pattern_names: matches.get_flag("NO_LAMBDA_PATTERN_NAMES"),
+        no_top_lambda_pattern_names: matches.get_flag("NO_TOP_LAMBDA_PATTERN_NAMES"),
         no_underscore: matches.get_flag("NO_UNDERSCORE"),
         warn_used_underscore: matches.get_flag("WARN_USED_UNDERSCORE"),
+        warn_unused_with: matches.get_flag("WARN_UNUSED_WITH"),
+        warn_unused_rec: matches.get_flag("WARN_UNUSED_REC"),
+        warn_unused_attrs: matches.get_flag("WARN_UNUSED_ATTRS"),
+        warn_shadowing: matches.get_flag("WARN_SHADOWING"),
     };
-    let quiet = matches.get_flag("QUIET");
+    let diff = matches.get_flag("DIFF");
+    // the diff is the output
+    let quiet = matches.get_flag("QUIET") || diff;
     let edit = matches.get_flag("EDIT");
+    let mut has_diff = false;
     let is_visible = if matches.get_flag("HIDDEN") {
         |_: &walkdir::DirEntry| true
     } else {
@@ -149,17 +258,39 @@ fn main() {
     let output_format = matches
         .get_one::<String>("OUTPUT_FORMAT")
         .map(String::as_str);
+    let column_unit = match matches.get_one::<String>("COLUMNS").map(String::as_str) {
+        Some("chars") => report::ColumnUnit::Chars,
+        Some("utf16") => report::ColumnUnit::Utf16,
+        _ => report::ColumnUnit::Bytes,
+    };
     let output_format = match output_format {
         Some("human-readable") => OutputFormat::HumanReadable,
+        Some("checkstyle") => OutputFormat::Checkstyle,
+        Some("github") => OutputFormat::Github,
+        Some("junit") => OutputFormat::Junit,
+        Some("html") => OutputFormat::Html,
+        Some("short") => OutputFormat::Short,
         #[cfg(feature = "json-out")]
         Some("json") => OutputFormat::Json,
+        #[cfg(feature = "json-out")]
+        Some("sarif") => OutputFormat::Sarif,
+        #[cfg(feature = "json-out")]
+        Some("codeclimate") => OutputFormat::CodeClimate,
         #[cfg(not(feature = "json-out"))]
-        Some("json") => panic!(
+        Some("json" | "sarif" | "codeclimate") => panic!(
             "`deadnix` needs to be built with `json-out` feature flag for JSON output format."
         ),
         _ => panic!("Unknown output format."), // clap shouldn't allow this case
     };
 
+    #[cfg(feature = "json-out")]
+    let mut sarif = report::Sarif::new();
+    let mut checkstyle = report::Checkstyle::new();
+    #[cfg(feature = "json-out")]
+    let mut code_climate = report::CodeClimate::new();
+    let mut junit = report::Junit::new();
+    let mut html = report::Html::new();
+
     let file_paths = matches
         .get_many::<String>("FILE_PATHS")
         .expect("FILE_PATHS");
@@ -193,22 +324,72 @@ fn main() {
                         eprintln!("Error stating file {path}: {error}");
                     }
 
+                    // keep stdout a valid report
+                    #[cfg(feature = "json-out")]
+                    OutputFormat::CodeClimate => {
+                        eprintln!("Error stating file {path}: {error}");
+                    }
+
                     #[cfg(feature = "json-out")]
                     OutputFormat::Json => println!(
                         "{}",
                         json!({
+                            "schemaVersion": deadnix::JSON_SCHEMA_VERSION,
                             "file": path,
                             "results": [{
                                 "message": format!("{error}"),
                             }],
                         })
                     ),
+
+                    #[cfg(feature = "json-out")]
+                    OutputFormat::Sarif => {
+                        sarif.add_error(path, &format!("Error stating file {path}: {error}"));
+                    }
+
+                    OutputFormat::Checkstyle => {
+                        checkstyle.add_error(path, &format!("Error stating file {path}: {error}"));
+                    }
+
+                    OutputFormat::Github => {
+                        report::print_github_error(
+                            path,
+                            &format!("Error stating file {path}: {error}"),
+                        );
+                    }
+
+                    OutputFormat::Junit => {
+                        junit.add_error(path, &format!("Error stating file {path}: {error}"));
+                    }
+
+                    OutputFormat::Html => {
+                        html.add_error(path, &format!("Error stating file {path}: {error}"));
+                    }
+
+                    OutputFormat::Short => {
+                        report::print_short_error(path, &format!("{error}"));
+                    }
                 }
                 Box::new([].into_iter())
             }
         };
         files
     });
+    let files = files.collect::<Vec<_>>();
+
+    let imports = matches.get_flag("FOLLOW_IMPORTS").then(|| {
+        let mut imports = Imports::new();
+        for file in &files {
+            if let Ok(content) = fs::read_to_string(file) {
+                let ast = rnix::Root::parse(&content);
+                if ast.errors().is_empty() {
+                    imports.add(Path::new(file), &ast.syntax());
+                }
+            }
+        }
+        imports
+    });
+
     for file in files {
         let content = match fs::read_to_string(&file) {
             Ok(content) => content,
@@ -218,16 +399,50 @@ fn main() {
                         eprintln!("Error reading file {file}: {error}");
                     }
 
+                    #[cfg(feature = "json-out")]
+                    OutputFormat::CodeClimate => {
+                        eprintln!("Error reading file {file}: {error}");
+                    }
+
                     #[cfg(feature = "json-out")]
                     OutputFormat::Json => println!(
                         "{}",
                         json!({
+                            "schemaVersion": deadnix::JSON_SCHEMA_VERSION,
                             "file": file,
                             "results": [{
                                 "message": format!("{error}"),
                             }],
                         })
                     ),
+
+                    #[cfg(feature = "json-out")]
+                    OutputFormat::Sarif => {
+                        sarif.add_error(&file, &format!("Error reading file {file}: {error}"));
+                    }
+
+                    OutputFormat::Checkstyle => {
+                        checkstyle.add_error(&file, &format!("Error reading file {file}: {error}"));
+                    }
+
+                    OutputFormat::Github => {
+                        report::print_github_error(
+                            &file,
+                            &format!("Error reading file {file}: {error}"),
+                        );
+                    }
+
+                    OutputFormat::Junit => {
+                        junit.add_error(&file, &format!("Error reading file {file}: {error}"));
+                    }
+
+                    OutputFormat::Html => {
+                        html.add_error(&file, &format!("Error reading file {file}: {error}"));
+                    }
+
+                    OutputFormat::Short => {
+                        report::print_short_error(&file, &format!("{error}"));
+                    }
                 }
                 continue;
             }
@@ -244,10 +459,18 @@ fn main() {
                     }
                 }
 
+                #[cfg(feature = "json-out")]
+                OutputFormat::CodeClimate => {
+                    for error in errors {
+                        eprintln!("Error parsing file {file}: {error}");
+                    }
+                }
+
                 #[cfg(feature = "json-out")]
                 OutputFormat::Json => println!(
                     "{}",
                     json!({
+                        "schemaVersion": deadnix::JSON_SCHEMA_VERSION,
                         "file": file,
                         "results": errors.iter()
                             .map(|error| json!({
@@ -256,33 +479,172 @@ fn main() {
                             .collect::<Vec<_>>(),
                     })
                 ),
+
+                #[cfg(feature = "json-out")]
+                OutputFormat::Sarif => {
+                    for error in errors {
+                        sarif.add_error(&file, &format!("Error parsing file {file}: {error}"));
+                    }
+                }
+
+                OutputFormat::Checkstyle => {
+                    checkstyle.add_parse_errors(&file, &content, errors);
+                }
+
+                OutputFormat::Github => {
+                    report::print_github_parse_errors(&file, &content, errors);
+                }
+
+                OutputFormat::Junit => {
+                    junit.add_parse_errors(&file, &content, errors);
+                }
+
+                OutputFormat::Html => {
+                    html.add_parse_errors(&file, &content, errors);
+                }
+
+                OutputFormat::Short => {
+                    report::print_short_parse_errors(&file, &content, errors, column_unit);
+                }
             }
             continue;
         }
 
-        let results = settings.find_dead_code(&ast.syntax());
+        let results = match &imports {
+            Some(imports) => {
+                settings.find_dead_code_with_imports(&ast.syntax(), Path::new(&file), imports)
+            }
+            None => settings.find_dead_code(&ast.syntax()),
+        };
         report_count += results.len();
-        if !quiet && !results.is_empty() {
+        if !quiet {
             match output_format {
+                // passing files are testcases, too
+                OutputFormat::Junit => {
+                    junit.add(&file, &content, &results);
+                }
+
+                OutputFormat::Html => {
+                    html.add(&file, &content, &results);
+                }
+
+                _ if results.is_empty() => {}
+
                 OutputFormat::HumanReadable => {
-                    crate::report::print(file.clone(), &content, &results);
+                    report::print(file.clone(), &content, &results);
                 }
 
                 #[cfg(feature = "json-out")]
                 OutputFormat::Json => {
-                    crate::report::print_json(&file.clone(), &content, &results);
+                    report::print_json(&file.clone(), &content, &results);
+                }
+
+                #[cfg(feature = "json-out")]
+                OutputFormat::Sarif => {
+                    sarif.add(&file, &content, &results);
+                }
+
+                OutputFormat::Checkstyle => {
+                    checkstyle.add(&file, &content, &results);
+                }
+
+                OutputFormat::Github => {
+                    report::print_github(&file, &content, &results);
+                }
+
+                OutputFormat::Short => {
+                    report::print_short(&file, &content, &results, column_unit);
+                }
+
+                #[cfg(feature = "json-out")]
+                OutputFormat::CodeClimate => {
+                    code_climate.add(&file, &content, &results);
                 }
             }
         }
-        if edit {
-            let (new_ast, has_changes) = crate::edit::edit_dead_code(&content, results.into_iter());
+        if diff {
+            let diff = diff_dead_code(&file, &content, results.into_iter());
+            if !diff.is_empty() {
+                print!("{diff}");
+                has_diff = true;
+            }
+        } else if edit {
+            let (new_ast, has_changes) = edit_dead_code(&content, results.into_iter());
             if has_changes {
                 fs::write(file, new_ast).expect("fs::write");
             }
         }
     }
 
-    if fail_on_reports && report_count > 0 {
+    match output_format {
+        _ if diff => {}
+        #[cfg(feature = "json-out")]
+        OutputFormat::Sarif => sarif.print(),
+        OutputFormat::Checkstyle => checkstyle.print(),
+        #[cfg(feature = "json-out")]
+        OutputFormat::CodeClimate => code_climate.print(),
+        OutputFormat::Junit => junit.print(),
+        OutputFormat::Html => html.print(),
+        _ => {}
+    }
+
+    if has_diff || (fail_on_reports && report_count > 0) {
         std::process::exit(1);
     }
 }
+
+/// Apply the findings in the file at `path`, `false` if any of them
+/// could not be applied
+#[cfg(feature = "json-out")]
+fn apply(path: &str) -> bool {
+    let findings = match fs::read_to_string(path)
+        .map_err(|error| format!("Error reading file {path}: {error}"))
+        .and_then(|json| {
+            deadnix::apply::read_findings(&json).map_err(|error| format!("{path}: {error}"))
+        }) {
+        Ok(findings) => findings,
+        Err(error) => {
+            eprintln!("{error}");
+            return false;
+        }
+    };
+
+    // for findings of --follow-imports, before any file is edited
+    let mut imports = Imports::new();
+    for findings in &findings {
+        if findings.entries.is_empty() {
+            continue;
+        }
+        if let Ok(content) = fs::read_to_string(&findings.file) {
+            let ast = rnix::Root::parse(&content);
+            if ast.errors().is_empty() {
+                imports.add_imported(Path::new(&findings.file), &ast.syntax());
+            }
+        }
+    }
+
+    let mut success = true;
+    for findings in &findings {
+        if findings.entries.is_empty() {
+            continue;
+        }
+        let file = &findings.file;
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(error) => {
+                eprintln!("Error reading file {file}: {error}");
+                success = false;
+                continue;
+            }
+        };
+        let (new_ast, has_changes, stale) = findings.apply(&content, &imports);
+        for entry in stale {
+            eprintln!("Stale finding in {file}, not applied: {entry}");
+            success = false;
+        }
+        if has_changes {
+            fs::write(file, new_ast).expect("fs::write");
+        }
+    }
+    success
+}
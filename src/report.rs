@@ -1,13 +1,28 @@
-//! Dead code reporting in human-readable and JSON formats
+//! Dead code reporting in human-readable, short, JSON, SARIF,
+//! Checkstyle, GitHub Actions, Code Climate, `JUnit` and HTML formats
 
-use crate::dead_code::DeadCode;
+pub use crate::lines::ColumnUnit;
+use crate::{dead_code::DeadCode, lines::Lines};
+#[cfg(feature = "json-out")]
+use crate::{
+    edit::{dead_code_edits, standalone_edits},
+    schema::{findings, JSON_SCHEMA_VERSION},
+};
 use ariadne::{sources, Config, Label, Report, ReportKind};
-use rnix::TextSize;
+#[cfg(feature = "json-out")]
+use rnix::ast::AttrpathValue;
+use rnix::{parser::ParseError, TextRange, TextSize};
+#[cfg(feature = "json-out")]
 use rowan::ast::AstNode;
-use std::env;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Write,
+    path::Path,
+};
 
 #[cfg(feature = "json-out")]
-use serde_json::json;
+use serde_json::{json, Value};
 
 /// Build a report and print it to stdout
 ///
@@ -15,7 +30,7 @@ use serde_json::json;
 pub fn print(file: String, content: &str, results: &[DeadCode]) {
     let no_color = env::var("NO_COLOR").is_ok();
 
-    let first_result_range = results[0].binding.name.syntax().text_range();
+    let first_result_range = results[0].binding.name.text_range();
     let mut builder = Report::build(
         ReportKind::Warning,
         file.clone(),
@@ -24,37 +39,61 @@ pub fn print(file: String, content: &str, results: &[DeadCode]) {
     .with_config(Config::default().with_compact(true).with_color(!no_color))
     .with_message("Unused declarations were found.");
 
-    // advance into content to convert byte offsets into char offsets
+    // convert byte offsets into char offsets
+    let mut offsets = results
+        .iter()
+        .flat_map(|result| {
+            let shadowed = result
+                .shadowed
+                .as_ref()
+                .map(|(_, binding)| binding.name.text_range());
+            Some(result.binding.name.text_range())
+                .into_iter()
+                .chain(shadowed)
+                .flat_map(|range| [usize::from(range.start()), usize::from(range.end())])
+        })
+        .collect::<Vec<_>>();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let mut char_offsets = HashMap::with_capacity(offsets.len());
     let mut content_bytes = 0;
     let mut content_chars = 0usize;
     let mut char_bytes = content.chars().map(|c| usize::from(TextSize::of(c)));
+    for offset in offsets {
+        while content_bytes < offset {
+            content_bytes += char_bytes.next().unwrap();
+            content_chars += 1;
+        }
+        char_offsets.insert(offset, content_chars);
+    }
+    let char_range = |range: TextRange| {
+        char_offsets[&usize::from(range.start())]..char_offsets[&usize::from(range.end())]
+    };
+
     // reverse order to avoid overlapping lanes
     let mut order = results.len();
     for result in results {
         order -= 1;
 
-        let range = result.binding.name.syntax().text_range();
-        let start_byte = usize::from(range.start());
-        while content_bytes < start_byte {
-            content_bytes += char_bytes.next().unwrap();
-            content_chars += 1;
-        }
-        let start_char = content_chars;
-        let end_byte = usize::from(range.end());
-        while content_bytes < end_byte {
-            content_bytes += char_bytes.next().unwrap();
-            content_chars += 1;
-        }
-        let end_char = content_chars;
-
         // add report label
-        let mut label = Label::new((file.clone(), start_char..end_char))
+        let mut label = Label::new((file.clone(), char_range(result.binding.name.text_range())))
             .with_message(format!("{result}"))
             .with_order(order as i32);
         if !no_color {
             label = label.with_color(result.scope.color());
         }
         builder = builder.with_label(label);
+
+        // point to the outer binding that is shadowed
+        if let Some((scope, binding)) = &result.shadowed {
+            let mut label = Label::new((file.clone(), char_range(binding.name.text_range())))
+                .with_message(format!("Shadowed {scope}: {}", binding.name))
+                .with_order(order as i32);
+            if !no_color {
+                label = label.with_color(scope.color());
+            }
+            builder = builder.with_label(label);
+        }
     }
 
     // print
@@ -64,37 +103,811 @@ pub fn print(file: String, content: &str, results: &[DeadCode]) {
         .unwrap();
 }
 
+/// The location of a syntax error, if it has one
+fn error_range(error: &ParseError) -> Option<TextRange> {
+    match error {
+        ParseError::Unexpected(range)
+        | ParseError::UnexpectedExtra(range)
+        | ParseError::UnexpectedWanted(_, range, _)
+        | ParseError::UnexpectedDoubleBind(range)
+        | ParseError::DuplicatedArgs(range, _) => Some(*range),
+        _ => None,
+    }
+}
+
+/// Escape text for XML attributes and character data
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Print dead code to stdout in JSON
+///
+/// The `results` have the structure of the [`serde::Serialize`] impl of
+/// [`DeadCode`], see [`JSON_SCHEMA_VERSION`].
 #[cfg(feature = "json-out")]
 pub fn print_json(file: &str, content: &str, results: &[DeadCode]) {
-    let mut offset = 0;
-    let mut offsets = vec![offset];
-    while let Some(next) = content[offset..].find('\n') {
-        offset += next + 1;
-        offsets.push(offset);
-    }
+    println!("{}", json_report(file, content, results));
+}
 
-    let json = json!({
+/// The JSON object of [`print_json()`]
+#[cfg(feature = "json-out")]
+pub(crate) fn json_report(file: &str, content: &str, results: &[DeadCode]) -> Value {
+    let findings = findings(content, results);
+    json!({
+        "schemaVersion": JSON_SCHEMA_VERSION,
         "file": file,
-        "results": results.iter().map(|result| {
-            let range = result.binding.name.syntax().text_range();
-            let start = usize::from(range.start());
-            let mut line_number = 0;
-            let mut line_offset = 0;
-            for &offset in &offsets {
-                if start < offset {
-                    break;
-                }
-                line_number += 1;
-                line_offset = offset;
-            }
+        "results": findings.results,
+        "edits": findings.edits,
+    })
+}
+
+/// Kinds of findings by [`DeadCode::rule_id()`]
+const RULES: [(&str, &str); 9] = [
+    ("unused-let-binding", "Unused let binding"),
+    ("unused-legacy-let-binding", "Unused legacy let binding"),
+    ("unused-lambda-arg", "Unused lambda argument"),
+    ("unused-lambda-pattern", "Unused lambda pattern name"),
+    (
+        "unused-let-attribute",
+        "Unused attribute of a let-bound attrset",
+    ),
+    ("unused-with", "Unused with expression"),
+    ("superfluous-rec", "Superfluous rec attrset"),
+    ("used-underscore", "Used binding that starts with _"),
+    ("shadowing", "Binding shadows an outer binding"),
+];
+
+/// A SARIF 2.1.0 log with one run for all files
+#[cfg(feature = "json-out")]
+#[derive(Debug, Default)]
+pub struct Sarif {
+    results: Vec<serde_json::Value>,
+    notifications: Vec<serde_json::Value>,
+}
+
+#[cfg(feature = "json-out")]
+impl Sarif {
+    /// An empty log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the dead code of one file
+    pub fn add(&mut self, file: &str, content: &str, results: &[DeadCode]) {
+        let lines = Lines::new(content);
+        let region = |range: TextRange| {
+            let (start_line, start_column) = lines.position(range.start());
+            let (end_line, end_column) = lines.position(range.end());
             json!({
-                "message": format!("{result}"),
-                "line": line_number,
-                "column": start - line_offset + 1,
-                "endColumn": usize::from(range.end()) - line_offset + 1,
+                "startLine": start_line,
+                "startColumn": start_column,
+                "endLine": end_line,
+                "endColumn": end_column,
             })
-        }).collect::<serde_json::Value>(),
-    });
-    println!("{json}");
+        };
+
+        let edits = standalone_edits(content, results, &dead_code_edits(content, results));
+        for (result, edits) in results.iter().zip(edits) {
+            let rule_id = result.rule_id();
+            let mut json = json!({
+                "ruleId": rule_id,
+                "ruleIndex": RULES.iter().position(|(id, _)| *id == rule_id),
+                "level": "warning",
+                "message": { "text": result.to_string() },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file },
+                        "region": region(result.binding.name.text_range()),
+                    },
+                }],
+            });
+            let replacements = edits
+                .into_iter()
+                .map(|edit| {
+                    let deleted = TextRange::new(
+                        TextSize::try_from(edit.start).expect("TextSize"),
+                        TextSize::try_from(edit.end).expect("TextSize"),
+                    );
+                    json!({
+                        "deletedRegion": region(deleted),
+                        "insertedContent": { "text": edit.replacement },
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !replacements.is_empty() {
+                json["fixes"] = json!([{
+                    "description": { "text": format!("Remove {}", result.binding.name) },
+                    "artifactChanges": [{
+                        "artifactLocation": { "uri": file },
+                        "replacements": replacements,
+                    }],
+                }]);
+            }
+            self.results.push(json);
+        }
+    }
+
+    /// Add an error about a file that could not be analyzed
+    pub fn add_error(&mut self, file: &str, message: &str) {
+        self.notifications.push(json!({
+            "level": "error",
+            "message": { "text": message },
+            "locations": [{
+                "physicalLocation": {
+                    "artifactLocation": { "uri": file },
+                },
+            }],
+        }));
+    }
+
+    /// Print the log to stdout
+    pub fn print(self) {
+        println!("{}", self.log());
+    }
+
+    pub(crate) fn log(self) -> serde_json::Value {
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "deadnix",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                        "rules": RULES.iter().map(|(id, description)| json!({
+                            "id": id,
+                            "shortDescription": { "text": description },
+                        })).collect::<Vec<_>>(),
+                    },
+                },
+                "columnKind": "unicodeCodePoints",
+                "invocations": [{
+                    "executionSuccessful": self.notifications.is_empty(),
+                    "toolExecutionNotifications": self.notifications,
+                }],
+                "results": self.results,
+            }],
+        })
+    }
+}
+
+/// A Checkstyle XML report of all files
+#[derive(Debug, Default)]
+pub struct Checkstyle {
+    files: Vec<String>,
+}
+
+impl Checkstyle {
+    /// An empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the dead code of one file
+    pub fn add(&mut self, file: &str, content: &str, results: &[DeadCode]) {
+        let lines = Lines::new(content);
+        let errors = results.iter().map(|result| {
+            let (line, column) = lines.position(result.binding.name.text_range().start());
+            format!(
+                "    <error line=\"{line}\" column=\"{column}\" severity=\"warning\" message=\"{}\" source=\"deadnix.{}.{}\"/>\n",
+                xml_escape(&result.to_string()),
+                result.scope.kind(),
+                result.status(),
+            )
+        });
+        self.add_file(file, errors);
+    }
+
+    /// Add an error about a file that could not be read
+    pub fn add_error(&mut self, file: &str, message: &str) {
+        let error = format!(
+            "    <error severity=\"error\" message=\"{}\" source=\"deadnix.read-error\"/>\n",
+            xml_escape(message),
+        );
+        self.add_file(file, [error]);
+    }
+
+    /// Add the syntax errors of a file that could not be parsed
+    pub fn add_parse_errors(&mut self, file: &str, content: &str, errors: &[ParseError]) {
+        let lines = Lines::new(content);
+        let errors = errors.iter().map(|error| {
+            let position = error_range(error)
+                .map(|range| {
+                    let (line, column) = lines.position(range.start());
+                    format!("line=\"{line}\" column=\"{column}\" ")
+                })
+                .unwrap_or_default();
+            format!(
+                "    <error {position}severity=\"error\" message=\"{}\" source=\"deadnix.parse-error\"/>\n",
+                xml_escape(&error.to_string()),
+            )
+        });
+        self.add_file(file, errors);
+    }
+
+    fn add_file(&mut self, file: &str, errors: impl IntoIterator<Item = String>) {
+        let mut xml = format!("  <file name=\"{}\">\n", xml_escape(file));
+        xml.extend(errors);
+        xml.push_str("  </file>\n");
+        self.files.push(xml);
+    }
+
+    /// Print the report to stdout
+    pub fn print(&self) {
+        print!("{}", self.xml());
+    }
+
+    pub(crate) fn xml(&self) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n{}</checkstyle>\n",
+            self.files.concat(),
+        )
+    }
+}
+
+/// Print dead code to stdout with a line per finding, like compilers do
+pub fn print_short(file: &str, content: &str, results: &[DeadCode], unit: ColumnUnit) {
+    print!("{}", short_warnings(file, content, results, unit));
+}
+
+/// Print an error about a file that could not be read in the short
+/// format
+pub fn print_short_error(file: &str, message: &str) {
+    println!("{file}: error: {message}");
+}
+
+/// Print the syntax errors of a file in the short format
+pub fn print_short_parse_errors(
+    file: &str,
+    content: &str,
+    errors: &[ParseError],
+    unit: ColumnUnit,
+) {
+    print!("{}", short_parse_errors(file, content, errors, unit));
+}
+
+pub(crate) fn short_warnings(
+    file: &str,
+    content: &str,
+    results: &[DeadCode],
+    unit: ColumnUnit,
+) -> String {
+    let lines = Lines::new(content);
+    results
+        .iter()
+        .map(|result| {
+            let (line, column) = lines.position_in(result.binding.name.text_range().start(), unit);
+            format!(
+                "{file}:{line}:{column}: warning: {result} [{}]\n",
+                result.scope.kind()
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+pub(crate) fn short_parse_errors(
+    file: &str,
+    content: &str,
+    errors: &[ParseError],
+    unit: ColumnUnit,
+) -> String {
+    let lines = Lines::new(content);
+    errors
+        .iter()
+        .map(|error| match error_range(error) {
+            Some(range) => {
+                let (line, column) = lines.position_in(range.start(), unit);
+                format!("{file}:{line}:{column}: error: {error}\n")
+            }
+            None => format!("{file}: error: {error}\n"),
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Print dead code to stdout as GitHub Actions workflow commands
+pub fn print_github(file: &str, content: &str, results: &[DeadCode]) {
+    print!("{}", github_warnings(file, content, results));
+}
+
+/// Print an error about a file that could not be read as a GitHub
+/// Actions workflow command
+pub fn print_github_error(file: &str, message: &str) {
+    println!(
+        "::error file={},title=deadnix::{}",
+        github_property(file),
+        github_data(message)
+    );
+}
+
+/// Print the syntax errors of a file as GitHub Actions workflow commands
+pub fn print_github_parse_errors(file: &str, content: &str, errors: &[ParseError]) {
+    print!("{}", github_parse_errors(file, content, errors));
+}
+
+pub(crate) fn github_warnings(file: &str, content: &str, results: &[DeadCode]) -> String {
+    let lines = Lines::new(content);
+    results
+        .iter()
+        .map(|result| {
+            let (line, column, end_column) = lines.columns(result.binding.name.text_range());
+            format!(
+                "::warning file={},line={line},col={column},endColumn={end_column},title={}::{}\n",
+                github_property(file),
+                result.rule_id(),
+                github_data(&result.to_string()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+pub(crate) fn github_parse_errors(file: &str, content: &str, errors: &[ParseError]) -> String {
+    let lines = Lines::new(content);
+    errors
+        .iter()
+        .map(|error| {
+            let position = error_range(error)
+                .map(|range| {
+                    let (line, column, _) = lines.columns(range);
+                    format!(",line={line},col={column}")
+                })
+                .unwrap_or_default();
+            format!(
+                "::error file={}{position},title=deadnix::{}\n",
+                github_property(file),
+                github_data(&error.to_string()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Escape the message of a workflow command
+fn github_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command
+fn github_property(text: &str) -> String {
+    github_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// A Code Climate JSON report of all files, as used by GitLab Code
+/// Quality
+#[cfg(feature = "json-out")]
+#[derive(Debug, Default)]
+pub struct CodeClimate {
+    issues: Vec<serde_json::Value>,
+}
+
+#[cfg(feature = "json-out")]
+impl CodeClimate {
+    /// An empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the dead code of one file
+    pub fn add(&mut self, file: &str, content: &str, results: &[DeadCode]) {
+        let lines = Lines::new(content);
+        let mut occurrences = HashMap::new();
+        for result in results {
+            // identify the finding by its place in the syntax rather
+            // than by its position, which shifts with unrelated edits
+            let key = format!(
+                "{file}\0{}\0{}\0{}\0{}",
+                result.scope.kind(),
+                result.rule_id(),
+                result.binding.name,
+                scope_path(result),
+            );
+            let occurrence = occurrences.entry(key.clone()).or_insert(0);
+            let fingerprint = fnv1a(&format!("{key}\0{occurrence}"));
+            *occurrence += 1;
+
+            let (line, _) = lines.line(result.binding.name.text_range().start().into());
+            self.issues.push(json!({
+                "type": "issue",
+                "description": result.to_string(),
+                "check_name": result.rule_id(),
+                "categories": ["Clarity"],
+                "fingerprint": format!("{fingerprint:016x}"),
+                "severity": "minor",
+                "location": {
+                    "path": file,
+                    "lines": { "begin": line },
+                },
+            }));
+        }
+    }
+
+    /// Print the report to stdout
+    pub fn print(self) {
+        println!("{}", self.issues());
+    }
+
+    pub(crate) fn issues(self) -> serde_json::Value {
+        serde_json::Value::Array(self.issues)
+    }
+}
+
+/// The attribute paths that lead to a finding, like `a.b.c`
+#[cfg(feature = "json-out")]
+fn scope_path(result: &DeadCode) -> String {
+    let name = result.binding.name.syntax();
+    let mut path = name
+        .ancestors()
+        .filter_map(AttrpathValue::cast)
+        .filter_map(|attrpath_value| attrpath_value.attrpath())
+        // skip the declaration of the binding itself
+        .filter(|attrpath| {
+            !attrpath
+                .syntax()
+                .text_range()
+                .contains_range(name.text_range())
+        })
+        .map(|attrpath| attrpath.syntax().text().to_string())
+        .collect::<Vec<_>>();
+    path.reverse();
+    path.join(".")
+}
+
+/// 64-bit FNV-1a hash, which is stable unlike [`std::hash::DefaultHasher`]
+#[cfg(feature = "json-out")]
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A `JUnit` XML report with a testcase per file
+#[derive(Debug, Default)]
+pub struct Junit {
+    testcases: Vec<String>,
+    failures: usize,
+    errors: usize,
+}
+
+impl Junit {
+    /// An empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, which fails if there is dead code
+    pub fn add(&mut self, file: &str, content: &str, results: &[DeadCode]) {
+        let lines = Lines::new(content);
+        let failures = results.iter().map(|result| {
+            let (line, column) = lines.position(result.binding.name.text_range().start());
+            let message = xml_escape(&result.to_string());
+            format!(
+                "      <failure message=\"{message}\" type=\"{}\">{}:{line}:{column}: {message}</failure>\n",
+                result.rule_id(),
+                xml_escape(file),
+            )
+        });
+        if !results.is_empty() {
+            self.failures += 1;
+        }
+        self.add_testcase(file, failures);
+    }
+
+    /// Add a file that could not be read
+    pub fn add_error(&mut self, file: &str, message: &str) {
+        let message = xml_escape(message);
+        let error =
+            format!("      <error message=\"{message}\" type=\"read-error\">{message}</error>\n");
+        self.errors += 1;
+        self.add_testcase(file, [error]);
+    }
+
+    /// Add a file that could not be parsed
+    pub fn add_parse_errors(&mut self, file: &str, content: &str, errors: &[ParseError]) {
+        let lines = Lines::new(content);
+        let errors = errors.iter().map(|error| {
+            let message = xml_escape(&error.to_string());
+            let position = error_range(error)
+                .map(|range| {
+                    let (line, column) = lines.position(range.start());
+                    format!(":{line}:{column}")
+                })
+                .unwrap_or_default();
+            format!(
+                "      <error message=\"{message}\" type=\"parse-error\">{}{position}: {message}</error>\n",
+                xml_escape(file),
+            )
+        });
+        self.errors += 1;
+        self.add_testcase(file, errors);
+    }
+
+    fn add_testcase(&mut self, file: &str, children: impl IntoIterator<Item = String>) {
+        let mut children = children.into_iter().peekable();
+        let name = xml_escape(file);
+        let xml = if children.peek().is_some() {
+            format!(
+                "    <testcase name=\"{name}\" classname=\"deadnix\">\n{}    </testcase>\n",
+                children.collect::<Vec<_>>().concat(),
+            )
+        } else {
+            format!("    <testcase name=\"{name}\" classname=\"deadnix\"/>\n")
+        };
+        self.testcases.push(xml);
+    }
+
+    /// Print the report to stdout
+    pub fn print(&self) {
+        print!("{}", self.xml());
+    }
+
+    pub(crate) fn xml(&self) -> String {
+        let counts = format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"{}\"",
+            self.testcases.len(),
+            self.failures,
+            self.errors,
+        );
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"deadnix\" {counts}>\n  <testsuite name=\"deadnix\" {counts}>\n{}  </testsuite>\n</testsuites>\n",
+            self.testcases.concat(),
+        )
+    }
+}
+
+/// Number of lines shown around each finding in the HTML report
+const HTML_CONTEXT_LINES: usize = 2;
+
+const HTML_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }
+td.count { text-align: right; }
+.finding { margin: 1em 0; }
+.message { font-weight: bold; }
+.error { color: #b00; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.lineno { color: #999; user-select: none; }
+mark { background: #fd6; }
+";
+
+/// A self-contained HTML report of all files
+#[derive(Debug, Default)]
+pub struct Html {
+    files: Vec<HtmlFile>,
+}
+
+#[derive(Debug)]
+struct HtmlFile {
+    name: String,
+    /// Number of findings by [`DeadCode::rule_id()`]
+    counts: BTreeMap<&'static str, usize>,
+    /// Rendered findings, or errors
+    body: String,
+    /// Could not be read or parsed
+    error: bool,
+}
+
+impl Html {
+    /// An empty report
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file with its dead code
+    pub fn add(&mut self, file: &str, content: &str, results: &[DeadCode]) {
+        let lines = Lines::new(content);
+        let mut counts = BTreeMap::new();
+        let mut body = String::new();
+        for result in results {
+            *counts.entry(result.rule_id()).or_insert(0) += 1;
+
+            let range = result.binding.name.text_range();
+            let (line, column) = lines.position(range.start());
+            write!(
+                body,
+                "<div class=\"finding\">\n<p class=\"message\">{line}:{column}: {}</p>\n",
+                xml_escape(&result.to_string()),
+            )
+            .unwrap();
+            if let Some((scope, binding)) = &result.shadowed {
+                let (line, column) = lines.position(binding.name.text_range().start());
+                writeln!(
+                    body,
+                    "<p>Shadowed {scope}: {} at {line}:{column}</p>",
+                    xml_escape(binding.name.as_str()),
+                )
+                .unwrap();
+            }
+            body.push_str(&html_context(&lines, range));
+            body.push_str("</div>\n");
+        }
+        self.files.push(HtmlFile {
+            name: file.to_string(),
+            counts,
+            body,
+            error: false,
+        });
+    }
+
+    /// Add a file that could not be read
+    pub fn add_error(&mut self, file: &str, message: &str) {
+        self.files.push(HtmlFile {
+            name: file.to_string(),
+            counts: BTreeMap::new(),
+            body: format!("<p class=\"error\">{}</p>\n", xml_escape(message)),
+            error: true,
+        });
+    }
+
+    /// Add a file that could not be parsed
+    pub fn add_parse_errors(&mut self, file: &str, content: &str, errors: &[ParseError]) {
+        let lines = Lines::new(content);
+        let mut body = String::new();
+        for error in errors {
+            let range = error_range(error);
+            let position = range
+                .map(|range| {
+                    let (line, column) = lines.position(range.start());
+                    format!("{line}:{column}: ")
+                })
+                .unwrap_or_default();
+            write!(
+                body,
+                "<div class=\"finding\">\n<p class=\"message error\">{position}{}</p>\n",
+                xml_escape(&error.to_string()),
+            )
+            .unwrap();
+            if let Some(range) = range {
+                body.push_str(&html_context(&lines, range));
+            }
+            body.push_str("</div>\n");
+        }
+        self.files.push(HtmlFile {
+            name: file.to_string(),
+            counts: BTreeMap::new(),
+            body,
+            error: true,
+        });
+    }
+
+    /// Print the report to stdout
+    pub fn print(&self) {
+        print!("{}", self.html());
+    }
+
+    pub(crate) fn html(&self) -> String {
+        let total = |file: &HtmlFile| file.counts.values().sum::<usize>();
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>deadnix report</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>deadnix report</h1>\n<p>{} findings in {} files</p>\n",
+            self.files.iter().map(total).sum::<usize>(),
+            self.files.len(),
+        );
+
+        // summary by directory
+        let mut directories = BTreeMap::<String, (usize, usize)>::new();
+        for file in &self.files {
+            let directory = Path::new(&file.name)
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .unwrap_or_default();
+            let (files, findings) = directories.entry(directory).or_default();
+            *files += 1;
+            *findings += total(file);
+        }
+        html.push_str("<h2>Directories</h2>\n<table>\n<tr><th>Directory</th><th>Files</th><th>Findings</th></tr>\n");
+        for (directory, (files, findings)) in &directories {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"count\">{files}</td><td class=\"count\">{findings}</td></tr>",
+                xml_escape(directory),
+            ).unwrap();
+        }
+        html.push_str("</table>\n");
+
+        // counts by kind of finding, for the kinds that occur
+        let rules = RULES
+            .iter()
+            .filter(|(id, _)| self.files.iter().any(|file| file.counts.contains_key(id)))
+            .collect::<Vec<_>>();
+        html.push_str("<h2>Files</h2>\n<table>\n<tr><th>File</th><th>Findings</th>");
+        for (_, description) in &rules {
+            write!(html, "<th>{description}</th>").unwrap();
+        }
+        html.push_str("</tr>\n");
+        for (index, file) in self.files.iter().enumerate() {
+            let name = xml_escape(&file.name);
+            if file.body.is_empty() {
+                write!(html, "<tr><td>{name}</td>").unwrap();
+            } else {
+                write!(html, "<tr><td><a href=\"#file-{index}\">{name}</a></td>").unwrap();
+            }
+            if file.error {
+                html.push_str("<td class=\"error\">error</td>");
+            } else {
+                write!(html, "<td class=\"count\">{}</td>", total(file)).unwrap();
+            }
+            for (id, _) in &rules {
+                write!(
+                    html,
+                    "<td class=\"count\">{}</td>",
+                    file.counts.get(id).copied().unwrap_or(0),
+                )
+                .unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+
+        // findings by file
+        for (index, file) in self.files.iter().enumerate() {
+            if file.body.is_empty() {
+                continue;
+            }
+            write!(
+                html,
+                "<section id=\"file-{index}\">\n<h2>{}</h2>\n{}</section>\n",
+                xml_escape(&file.name),
+                file.body,
+            )
+            .unwrap();
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// The source lines around `range`, with `range` highlighted
+fn html_context(lines: &Lines, range: TextRange) -> String {
+    let start = usize::from(range.start());
+    let end = usize::from(range.end());
+    let (first, _) = lines.line(start);
+    let (last, _) = lines.line(end);
+    let from = first.saturating_sub(HTML_CONTEXT_LINES).max(1);
+    let to = (last + HTML_CONTEXT_LINES)
+        .min(lines.line_count())
+        .max(last);
+
+    let mut html = String::from("<pre>");
+    for line_number in from..=to {
+        let line_start = lines.line_start(line_number);
+        let text = lines.text(line_number);
+        let line_end = line_start + text.len();
+        write!(html, "<span class=\"lineno\">{line_number:>5} </span>").unwrap();
+        if start < line_end.max(line_start + 1) && line_start < end {
+            // highlight the part of `range` on this line
+            let mark_start = start.clamp(line_start, line_end) - line_start;
+            let mark_end = end.clamp(line_start, line_end) - line_start;
+            write!(
+                html,
+                "{}<mark>{}</mark>{}",
+                xml_escape(&text[..mark_start]),
+                xml_escape(&text[mark_start..mark_end]),
+                xml_escape(&text[mark_end..]),
+            )
+            .unwrap();
+        } else {
+            html.push_str(&xml_escape(text));
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n");
+    html
 }
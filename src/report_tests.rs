@@ -0,0 +1,601 @@
+#![cfg(test)]
+
+#[cfg(feature = "json-out")]
+use crate::report::{CodeClimate, Sarif};
+use crate::{
+    dead_code::Settings,
+    report::{
+        github_parse_errors, github_warnings, short_parse_errors, short_warnings, Checkstyle,
+        ColumnUnit, Html, Junit,
+    },
+};
+#[cfg(feature = "json-out")]
+use serde_json::{json, Value};
+
+#[cfg(feature = "json-out")]
+fn sarif(settings: &Settings, content: &str) -> Value {
+    let ast = rnix::Root::parse(content);
+    assert_eq!(0, ast.errors().len());
+    let results = settings.find_dead_code(&ast.syntax());
+    let mut sarif = Sarif::new();
+    sarif.add("test.nix", content, &results);
+    sarif.log()
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_run() {
+    let log = sarif(&Settings::default(), "x: 23");
+    assert_eq!(log["version"], "2.1.0");
+    let runs = log["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0]["tool"]["driver"]["name"], "deadnix");
+    assert_eq!(runs[0]["invocations"][0]["executionSuccessful"], true);
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_rule_ids() {
+    let settings = Settings {
+        warn_used_underscore: true,
+        ..Settings::default()
+    };
+    let log = sarif(&settings, "{ a }: b: let c = 1; _d = 2; in _d");
+    let run = &log["runs"][0];
+    let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+    let results = run["results"].as_array().unwrap();
+    let rule_ids = results
+        .iter()
+        .map(|result| {
+            let index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], result["ruleId"]);
+            result["ruleId"].as_str().unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rule_ids,
+        [
+            "unused-lambda-pattern",
+            "unused-lambda-arg",
+            "unused-let-binding",
+            "used-underscore"
+        ]
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_region() {
+    let log = sarif(&Settings::default(), "let\n  s = \"ü\"; unused = 1;\nin s");
+    let result = &log["runs"][0]["results"][0];
+    assert_eq!(result["message"]["text"], "Unused let binding: unused");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"],
+        json!({
+            "artifactLocation": { "uri": "test.nix" },
+            "region": { "startLine": 2, "startColumn": 12, "endLine": 2, "endColumn": 18 },
+        })
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_fix() {
+    let log = sarif(&Settings::default(), "let\n  unused = 1;\nin 2");
+    let fixes = &log["runs"][0]["results"][0]["fixes"];
+    assert_eq!(
+        fixes[0]["artifactChanges"][0]["replacements"],
+        json!([{
+            "deletedRegion": { "startLine": 1, "startColumn": 1, "endLine": 3, "endColumn": 4 },
+            "insertedContent": { "text": "" },
+        }])
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_fix_each() {
+    // every finding has its own fix, even if all of them together
+    // remove the whole `let ... in`
+    let log = sarif(
+        &Settings::default(),
+        "let\n  a = 1;\n  inherit (x) b;\nin 2",
+    );
+    let results = log["runs"][0]["results"].as_array().unwrap();
+    assert_eq!(2, results.len());
+    for result in results {
+        assert_eq!(
+            1,
+            result["fixes"][0]["artifactChanges"][0]["replacements"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+    }
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_no_fix() {
+    let settings = Settings {
+        warn_used_underscore: true,
+        ..Settings::default()
+    };
+    let log = sarif(&settings, "let _a = 1; in _a");
+    let result = &log["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "used-underscore");
+    assert!(result.get("fixes").is_none());
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn sarif_errors() {
+    let mut sarif = Sarif::new();
+    sarif.add_error("test.nix", "Error parsing file test.nix");
+    let log = sarif.log();
+    let invocation = &log["runs"][0]["invocations"][0];
+    assert_eq!(invocation["executionSuccessful"], false);
+    assert_eq!(
+        invocation["toolExecutionNotifications"][0]["message"]["text"],
+        "Error parsing file test.nix"
+    );
+}
+
+fn checkstyle(content: &str) -> String {
+    let ast = rnix::Root::parse(content);
+    let mut checkstyle = Checkstyle::new();
+    if ast.errors().is_empty() {
+        let results = Settings::default().find_dead_code(&ast.syntax());
+        checkstyle.add("test.nix", content, &results);
+    } else {
+        checkstyle.add_parse_errors("test.nix", content, ast.errors());
+    }
+    checkstyle.xml()
+}
+
+#[test]
+fn checkstyle_results() {
+    assert_eq!(
+        checkstyle("let\n  s = \"ü\"; unused = 1;\nin x: s"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<checkstyle version="4.3">
+  <file name="test.nix">
+    <error line="2" column="12" severity="warning" message="Unused let binding: unused" source="deadnix.let-in.unused"/>
+    <error line="3" column="4" severity="warning" message="Unused lambda argument: x" source="deadnix.lambda-arg.unused"/>
+  </file>
+</checkstyle>
+"#
+    );
+}
+
+#[test]
+fn checkstyle_source_scope_kind() {
+    let content = "let _a = 1; b = 2; in b: _a + b";
+    let ast = rnix::Root::parse(content);
+    let settings = Settings {
+        warn_used_underscore: true,
+        warn_shadowing: true,
+        ..Settings::default()
+    };
+    let mut checkstyle = Checkstyle::new();
+    checkstyle.add("test.nix", content, &settings.find_dead_code(&ast.syntax()));
+    let xml = checkstyle.xml();
+    assert!(xml.contains(r#"source="deadnix.let-in.used-underscore""#));
+    assert!(xml.contains(r#"source="deadnix.let-in.unused""#));
+    assert!(xml.contains(r#"source="deadnix.lambda-arg.shadowing""#));
+}
+
+#[test]
+fn checkstyle_parse_errors() {
+    assert_eq!(
+        checkstyle("let a = 1;\n in ) a"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<checkstyle version="4.3">
+  <file name="test.nix">
+    <error line="2" column="5" severity="error" message="unexpected TOKEN_R_PAREN at 15..16, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]" source="deadnix.parse-error"/>
+  </file>
+</checkstyle>
+"#
+    );
+}
+
+#[test]
+fn checkstyle_read_error() {
+    let mut checkstyle = Checkstyle::new();
+    checkstyle.add_error("a&b.nix", "Error reading file a&b.nix: <denied>");
+    assert_eq!(
+        checkstyle.xml(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<checkstyle version="4.3">
+  <file name="a&amp;b.nix">
+    <error severity="error" message="Error reading file a&amp;b.nix: &lt;denied&gt;" source="deadnix.read-error"/>
+  </file>
+</checkstyle>
+"#
+    );
+}
+
+#[test]
+fn checkstyle_empty() {
+    assert_eq!(
+        Checkstyle::new().xml(),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"4.3\">\n</checkstyle>\n"
+    );
+}
+
+#[test]
+fn github_results() {
+    let content = "let\n  s = \"ü\"; unused = 1;\nin x: s";
+    let ast = rnix::Root::parse(content);
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    assert_eq!(
+        github_warnings("dir,a:b%.nix", content, &results),
+        "::warning file=dir%2Ca%3Ab%25.nix,line=2,col=13,endColumn=19,title=unused-let-binding::Unused let binding: unused
+::warning file=dir%2Ca%3Ab%25.nix,line=3,col=4,endColumn=5,title=unused-lambda-arg::Unused lambda argument: x
+"
+    );
+}
+
+#[test]
+fn github_parse_error() {
+    let content = "let a = 1;\n in ) a";
+    let ast = rnix::Root::parse(content);
+    assert_eq!(
+        github_parse_errors("test.nix", content, ast.errors()),
+        "::error file=test.nix,line=2,col=5,title=deadnix::unexpected TOKEN_R_PAREN at 15..16, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]\n"
+    );
+}
+
+#[cfg(feature = "json-out")]
+fn code_climate(content: &str) -> Vec<Value> {
+    let ast = rnix::Root::parse(content);
+    assert_eq!(0, ast.errors().len());
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    let mut code_climate = CodeClimate::new();
+    code_climate.add("test.nix", content, &results);
+    code_climate.issues().as_array().unwrap().clone()
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn code_climate_issue() {
+    let issues = code_climate("{\n  a = let unused = 1; in 2;\n}");
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["description"], "Unused let binding: unused");
+    assert_eq!(issues[0]["check_name"], "unused-let-binding");
+    assert_eq!(issues[0]["severity"], "minor");
+    assert_eq!(
+        issues[0]["location"],
+        json!({ "path": "test.nix", "lines": { "begin": 2 } })
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn code_climate_fingerprint_line_shift() {
+    let issues = code_climate("{\n  a = let unused = 1; in 2;\n}");
+    let shifted = code_climate("{\n  b = 1;\n\n  a =\n    let unused = 1; in 2;\n}");
+    assert_eq!(shifted[0]["location"]["lines"]["begin"], 5);
+    assert_eq!(issues[0]["fingerprint"], shifted[0]["fingerprint"]);
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn code_climate_fingerprint_distinct() {
+    let issues = code_climate(
+        "{ a = let unused = 1; in 2; b = let unused = 1; in 2; c = let unused = 1; in let unused = 2; in 3; }",
+    );
+    let mut fingerprints = issues
+        .iter()
+        .map(|issue| issue["fingerprint"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(fingerprints.len(), 4);
+    fingerprints.sort_unstable();
+    fingerprints.dedup();
+    assert_eq!(fingerprints.len(), 4);
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn code_climate_fingerprint_scope_kind() {
+    let settings = Settings {
+        warn_used_underscore: true,
+        ..Settings::default()
+    };
+    let let_fingerprint = |content: &str| {
+        let ast = rnix::Root::parse(content);
+        let mut code_climate = CodeClimate::new();
+        code_climate.add("test.nix", content, &settings.find_dead_code(&ast.syntax()));
+        let issues = code_climate.issues();
+        let issues = issues.as_array().unwrap();
+        issues
+            .iter()
+            .find(|issue| issue["description"] == "Used let binding: _a")
+            .unwrap()["fingerprint"]
+            .clone()
+    };
+    // not shifted by a finding of the same name in another kind of scope
+    assert_eq!(
+        let_fingerprint("[ ({ _a }: _a) (let _a = 1; in _a) ]"),
+        let_fingerprint("[ (let _a = 1; in _a) ]")
+    );
+}
+
+#[test]
+fn junit_testcases() {
+    let mut junit = Junit::new();
+    for (file, content) in [
+        ("dead.nix", "let\n  s = \"ü\"; unused = 1;\nin x: s"),
+        ("alive.nix", "let a = 1; in a"),
+        ("bad.nix", "let a = 1;\n in ) a"),
+    ] {
+        let ast = rnix::Root::parse(content);
+        if ast.errors().is_empty() {
+            let results = Settings::default().find_dead_code(&ast.syntax());
+            junit.add(file, content, &results);
+        } else {
+            junit.add_parse_errors(file, content, ast.errors());
+        }
+    }
+    junit.add_error("<missing>.nix", "Error reading file <missing>.nix");
+    assert_eq!(
+        junit.xml(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="deadnix" tests="4" failures="1" errors="2">
+  <testsuite name="deadnix" tests="4" failures="1" errors="2">
+    <testcase name="dead.nix" classname="deadnix">
+      <failure message="Unused let binding: unused" type="unused-let-binding">dead.nix:2:12: Unused let binding: unused</failure>
+      <failure message="Unused lambda argument: x" type="unused-lambda-arg">dead.nix:3:4: Unused lambda argument: x</failure>
+    </testcase>
+    <testcase name="alive.nix" classname="deadnix"/>
+    <testcase name="bad.nix" classname="deadnix">
+      <error message="unexpected TOKEN_R_PAREN at 15..16, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]" type="parse-error">bad.nix:2:5: unexpected TOKEN_R_PAREN at 15..16, wanted any of [TOKEN_L_PAREN, TOKEN_REC, TOKEN_L_BRACE, TOKEN_L_BRACK, TOKEN_STRING_START, TOKEN_IDENT]</error>
+    </testcase>
+    <testcase name="&lt;missing&gt;.nix" classname="deadnix">
+      <error message="Error reading file &lt;missing&gt;.nix" type="read-error">Error reading file &lt;missing&gt;.nix</error>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+}
+
+#[test]
+fn junit_empty() {
+    assert_eq!(
+        Junit::new().xml(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="deadnix" tests="0" failures="0" errors="0">
+  <testsuite name="deadnix" tests="0" failures="0" errors="0">
+  </testsuite>
+</testsuites>
+"#
+    );
+}
+
+fn html(files: &[(&str, &str)]) -> String {
+    let mut html = Html::new();
+    for (file, content) in files {
+        let ast = rnix::Root::parse(content);
+        if ast.errors().is_empty() {
+            let results = Settings::default().find_dead_code(&ast.syntax());
+            html.add(file, content, &results);
+        } else {
+            html.add_parse_errors(file, content, ast.errors());
+        }
+    }
+    html.html()
+}
+
+#[test]
+fn html_summary() {
+    let html = html(&[
+        ("a/dead.nix", "let\n  unused = 1;\nin x: 2\n"),
+        ("a/alive.nix", "let a = 1; in a"),
+        ("b/bad.nix", "let a = 1;\n in ) a"),
+    ]);
+    assert!(html.contains("<p>2 findings in 3 files</p>"));
+    assert!(html.contains(
+        "<tr><td>a</td><td class=\"count\">2</td><td class=\"count\">2</td></tr>\n<tr><td>b</td><td class=\"count\">1</td><td class=\"count\">0</td></tr>"
+    ));
+    assert!(html.contains("<tr><th>File</th><th>Findings</th><th>Unused let binding</th><th>Unused lambda argument</th></tr>"));
+    assert!(html.contains(
+        "<tr><td><a href=\"#file-0\">a/dead.nix</a></td><td class=\"count\">2</td><td class=\"count\">1</td><td class=\"count\">1</td></tr>"
+    ));
+    assert!(html.contains("<tr><td>a/alive.nix</td><td class=\"count\">0</td>"));
+    assert!(html
+        .contains("<tr><td><a href=\"#file-2\">b/bad.nix</a></td><td class=\"error\">error</td>"));
+    assert!(!html.contains("id=\"file-1\""));
+}
+
+#[test]
+fn html_context() {
+    let html = html(&[(
+        "test.nix",
+        "{\n  a = 1;\n  b = 2;\n  c = let \"<x>\" = 1; in 3;\n  d = 4;\n  e = 5;\n  f = 6;\n}\n",
+    )]);
+    assert!(html.contains(
+        r#"<p class="message">4:11: Unused let binding: &lt;x&gt;</p>
+<pre><span class="lineno">    2 </span>  a = 1;
+<span class="lineno">    3 </span>  b = 2;
+<span class="lineno">    4 </span>  c = let <mark>&quot;&lt;x&gt;&quot;</mark> = 1; in 3;
+<span class="lineno">    5 </span>  d = 4;
+<span class="lineno">    6 </span>  e = 5;
+</pre>"#
+    ));
+}
+
+#[test]
+fn short_results() {
+    let content = "let\n  s = \"ü\"; unused = 1;\nin x: s";
+    let ast = rnix::Root::parse(content);
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    assert_eq!(
+        short_warnings("test.nix", content, &results, ColumnUnit::Bytes),
+        "test.nix:2:13: warning: Unused let binding: unused [let-in]
+test.nix:3:4: warning: Unused lambda argument: x [lambda-arg]
+"
+    );
+    assert_eq!(
+        short_warnings("test.nix", content, &results, ColumnUnit::Chars),
+        "test.nix:2:12: warning: Unused let binding: unused [let-in]
+test.nix:3:4: warning: Unused lambda argument: x [lambda-arg]
+"
+    );
+}
+
+#[test]
+fn short_parse_error() {
+    let content = "let a = \"ü\";\n in ) a";
+    let ast = rnix::Root::parse(content);
+    assert!(
+        short_parse_errors("test.nix", content, ast.errors(), ColumnUnit::Chars)
+            .starts_with("test.nix:2:5: error: unexpected TOKEN_R_PAREN")
+    );
+
+    let content = "{";
+    let ast = rnix::Root::parse(content);
+    assert_eq!(
+        short_parse_errors("test.nix", content, ast.errors(), ColumnUnit::Bytes),
+        "test.nix: error: unexpected end of file\n"
+    );
+}
+
+#[cfg(feature = "json-out")]
+fn findings(settings: &Settings, content: &str) -> Vec<Value> {
+    let ast = rnix::Root::parse(content);
+    assert_eq!(0, ast.errors().len());
+    settings
+        .find_dead_code(&ast.syntax())
+        .iter()
+        .map(|result| serde_json::to_value(result).unwrap())
+        .collect()
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn json_finding() {
+    let findings = findings(&Settings::default(), "let\n  s = \"😀\"; unused = 1;\nin s");
+    assert_eq!(
+        findings,
+        [json!({
+            "message": "Unused let binding: unused",
+            "ruleId": "unused-let-binding",
+            "status": "unused",
+            "scope": "let-in",
+            "binding": {
+                "name": "unused",
+                "range": {
+                    "start": { "offset": 18, "line": 2, "column": 15, "charColumn": 12, "utf16Column": 13 },
+                    "end": { "offset": 24, "line": 2, "column": 21, "charColumn": 18, "utf16Column": 19 },
+                },
+                "declaration": {
+                    "start": { "offset": 18, "line": 2, "column": 15, "charColumn": 12, "utf16Column": 13 },
+                    "end": { "offset": 29, "line": 2, "column": 26, "charColumn": 23, "utf16Column": 24 },
+                },
+            },
+            "shadowed": null,
+            "edits": [{
+                "range": {
+                    "start": { "offset": 17, "line": 2, "column": 14, "charColumn": 11, "utf16Column": 12 },
+                    "end": { "offset": 29, "line": 2, "column": 26, "charColumn": 23, "utf16Column": 24 },
+                },
+                "replacement": "",
+            }],
+            "line": 2,
+            "column": 15,
+            "endColumn": 21,
+        })]
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn json_finding_edit_only() {
+    // without the removal of the `let ... in` that it leaves empty
+    let findings = findings(&Settings::default(), "let unused = 1; in 2");
+    assert_eq!(
+        findings[0]["edits"],
+        json!([{
+            "range": {
+                "start": { "offset": 3, "line": 1, "column": 4, "charColumn": 4, "utf16Column": 4 },
+                "end": { "offset": 15, "line": 1, "column": 16, "charColumn": 16, "utf16Column": 16 },
+            },
+            "replacement": "",
+        }])
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn json_finding_status() {
+    let settings = Settings {
+        warn_used_underscore: true,
+        warn_shadowing: true,
+        ..Settings::default()
+    };
+    let findings = findings(&settings, "let _a = 1; b = 2; in b: _a + b");
+    let summary = findings
+        .iter()
+        .map(|finding| {
+            (
+                finding["status"].as_str().unwrap(),
+                finding["scope"].as_str().unwrap(),
+                finding["binding"]["name"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            ("used-underscore", "let-in", "_a"),
+            ("unused", "let-in", "b"),
+            ("shadowing", "lambda-arg", "b"),
+        ]
+    );
+    assert_eq!(findings[2]["shadowed"]["scope"], "let-in");
+    assert_eq!(findings[2]["shadowed"]["binding"]["name"], "b");
+    assert_eq!(
+        findings[2]["shadowed"]["binding"]["range"]["start"]["offset"],
+        12
+    );
+}
+
+#[cfg(feature = "json-out")]
+#[test]
+fn json_edits() {
+    let content = "let\n  unused = 1;\n  inherit (x) a;\nin f: 2\n";
+    let ast = rnix::Root::parse(content);
+    let results = Settings::default().find_dead_code(&ast.syntax());
+    let report = crate::report::json_report("test.nix", content, &results);
+    let edits = report["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|finding| finding["edits"].as_array().unwrap().clone())
+        .collect::<Vec<_>>();
+    // each finding can be fixed on its own
+    assert_eq!(edits.iter().map(Vec::len).collect::<Vec<_>>(), [1, 1, 1]);
+    let apply = |edits: &[Value]| {
+        let mut edited = content.to_string();
+        for edit in edits.iter().rev() {
+            let start = edit["range"]["start"]["offset"].as_u64().unwrap() as usize;
+            let end = edit["range"]["end"]["offset"].as_u64().unwrap() as usize;
+            edited.replace_range(start..end, edit["replacement"].as_str().unwrap());
+        }
+        edited
+    };
+    assert_eq!(apply(&edits[0]), "let\n  inherit (x) a;\nin f: 2\n");
+    assert_eq!(apply(&edits[1]), "let\n  unused = 1;\nin f: 2\n");
+    assert_eq!(
+        apply(&edits[2]),
+        "let\n  unused = 1;\n  inherit (x) a;\nin _f: 2\n"
+    );
+
+    // applying the edits of the file gives the result of `--edit`,
+    // including the removal of the then empty `let ... in `
+    let edited = apply(report["edits"].as_array().unwrap());
+    let (expected, _) = crate::edit_dead_code(content, results.into_iter());
+    assert_eq!(edited, expected);
+    assert_eq!(edited, "_f: 2\n");
+}
@@ -0,0 +1,328 @@
+use crate::{
+    binding::{Binding, Name},
+    imports::Imports,
+    scope::Scope,
+};
+use rnix::{
+    ast::{Apply, Attr, AttrpathValue, Expr, Param, Pattern},
+    NixLanguage, SyntaxKind,
+};
+use rowan::{api::SyntaxNode, ast::AstNode};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// Functions that inspect the pattern names of their lambda argument
+const REFLECTING_FUNCTIONS: [&str; 4] = [
+    "callPackage",
+    "callPackages",
+    "functionArgs",
+    "makeOverridable",
+];
+
+/// An occurrence of a variable that refers to a [`Binding`]
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The `NODE_IDENT` of the occurrence, or the `NODE_STRING` in
+    /// `inherit "name";`
+    pub ident: SyntaxNode<NixLanguage>,
+    /// The body of the binding's [`Scope`] that contains the occurrence
+    pub body: SyntaxNode<NixLanguage>,
+}
+
+/// Symbol table of a syntax tree, built by walking it once
+///
+/// Maps every variable occurrence to the bindings that it may refer
+/// to. An inner binding shadows the outer ones with the same name,
+/// except in its `inherit` clauses, which are resolved in the
+/// enclosing scope.
+#[derive(Debug, Default)]
+pub struct Index {
+    /// References by the `decl_node` of their [`Binding`]
+    references: HashMap<SyntaxNode<NixLanguage>, Vec<Reference>>,
+    /// `with` nodes that may provide a variable to an occurrence
+    used_withs: HashSet<SyntaxNode<NixLanguage>>,
+    /// Names of occurrences that no enclosing scope binds
+    free: HashSet<String>,
+    /// Bindings of enclosing scopes by the `decl_node` of the
+    /// [`Binding`] that shadows them
+    shadowed: HashMap<SyntaxNode<NixLanguage>, (Scope, Binding)>,
+    /// Lambda patterns whose names are inspected by one of the
+    /// [`REFLECTING_FUNCTIONS`]
+    reflected: HashSet<SyntaxNode<NixLanguage>>,
+    /// Arguments passed to one of the [`REFLECTING_FUNCTIONS`]
+    reflected_args: HashSet<SyntaxNode<NixLanguage>>,
+    /// Arguments of `import ./path.nix arg` by the path literal
+    import_args: HashMap<SyntaxNode<NixLanguage>, String>,
+    /// Pattern names that the imported file consumes from the
+    /// argument of `import ./path.nix arg`
+    imported: HashMap<SyntaxNode<NixLanguage>, HashSet<String>>,
+    /// Dedup occurrences that are visited multiple times
+    seen: HashSet<(SyntaxNode<NixLanguage>, SyntaxNode<NixLanguage>)>,
+}
+
+/// Bindings of an enclosing [`Scope`] during the walk
+struct Frame {
+    /// Bindings by name
+    bindings: HashMap<String, Vec<Binding>>,
+    /// The body of the scope that is currently walked
+    body: Option<SyntaxNode<NixLanguage>>,
+    scope: Scope,
+}
+
+impl Index {
+    /// Resolve all variable occurrences below `node`
+    pub fn new(node: &SyntaxNode<NixLanguage>) -> Self {
+        let mut index = Index::default();
+        index.walk(node, &mut Vec::new());
+        index.seen.clear();
+        index.resolve_reflected();
+        index
+    }
+
+    /// All occurrences that refer to `binding`
+    pub fn references(&self, binding: &Binding) -> &[Reference] {
+        self.references
+            .get(&binding.decl_node)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The binding of an enclosing scope that has the same name as
+    /// `binding`, and its scope
+    pub fn shadowed(&self, binding: &Binding) -> Option<&(Scope, Binding)> {
+        self.shadowed.get(&binding.decl_node)
+    }
+
+    /// Are the names of `pattern` inspected by `callPackage`,
+    /// `functionArgs` and the like in this file?
+    pub fn is_reflected(&self, pattern: &Pattern) -> bool {
+        self.reflected.contains(pattern.syntax())
+    }
+
+    /// Look up the files of `import ./path.nix arg` expressions in a
+    /// file that resides in `dir`
+    pub fn resolve_imports(&mut self, dir: &Path, imports: &Imports) {
+        for (arg, import_path) in &self.import_args {
+            if let Some(consumed) = imports.consumed(dir, import_path) {
+                self.imported.insert(arg.clone(), consumed.clone());
+            }
+        }
+    }
+
+    /// The path literals of `import ./path.nix arg` expressions
+    pub fn import_paths(&self) -> impl Iterator<Item = &str> {
+        self.import_args.values().map(String::as_str)
+    }
+
+    /// The pattern names consumed by the file that `arg` is passed to
+    /// with `import ./path.nix arg`, if resolved
+    pub fn imported(&self, arg: &SyntaxNode<NixLanguage>) -> Option<&HashSet<String>> {
+        self.imported.get(arg)
+    }
+
+    /// Does `name` occur without being bound by a scope below the
+    /// indexed node?
+    pub fn is_free(&self, name: &str) -> bool {
+        self.free.contains(name)
+    }
+
+    /// Is any occurrence in the body of the `with` node left
+    /// unresolved by lexical bindings?
+    pub fn is_with_used(&self, with: &SyntaxNode<NixLanguage>) -> bool {
+        self.used_withs.contains(with)
+    }
+
+    fn walk(&mut self, node: &SyntaxNode<NixLanguage>, frames: &mut Vec<Frame>) {
+        if let Some(scope) = Scope::new(node) {
+            // `inherit` clauses take their names from the enclosing scope
+            for inherited in scope.inherited() {
+                self.walk(&inherited, frames);
+            }
+
+            let mut bindings = HashMap::<_, Vec<_>>::new();
+            for binding in scope.bindings() {
+                let outer = frames
+                    .iter()
+                    .rev()
+                    .find_map(|frame| Some((frame, frame.bindings.get(binding.name.as_str())?)));
+                if let Some((frame, outer)) = outer {
+                    self.shadowed.insert(
+                        binding.decl_node.clone(),
+                        (frame.scope.clone(), outer[0].clone()),
+                    );
+                }
+                bindings
+                    .entry(binding.name.as_str().to_string())
+                    .or_default()
+                    .push(binding);
+            }
+            let bodies = scope.bodies();
+            frames.push(Frame {
+                bindings,
+                body: None,
+                scope,
+            });
+            for body in bodies {
+                frames.last_mut().expect("frame").body = Some(body.clone());
+                self.walk(&body, frames);
+            }
+            frames.pop();
+        } else if node.kind() == SyntaxKind::NODE_IDENT {
+            self.resolve(node, &node.to_string(), frames);
+        } else if node.kind() == SyntaxKind::NODE_INHERIT {
+            // `inherit "name";` refers to a variable, too
+            for child in node.children() {
+                match Attr::cast(child.clone()).as_ref().and_then(Name::from_attr) {
+                    Some(name) if child.kind() == SyntaxKind::NODE_STRING => {
+                        self.resolve(&child, name.as_str(), frames);
+                    }
+                    _ => self.walk(&child, frames),
+                }
+            }
+        } else if node.kind() == SyntaxKind::NODE_ATTRPATH {
+            // Don't search for idents in keys, they introduce new
+            // scopes anyway. Except for `${...}` and `"..."`.
+            for child in node.children() {
+                if child.kind() == SyntaxKind::NODE_DYNAMIC
+                    || child.kind() == SyntaxKind::NODE_STRING
+                {
+                    self.walk(&child, frames);
+                }
+            }
+        } else if node.kind() == SyntaxKind::NODE_PAT_ENTRY {
+            // The name of a pattern entry is no occurrence, only its
+            // default value may be
+            for child in node.children().skip(1) {
+                self.walk(&child, frames);
+            }
+        } else {
+            if let Some(arg) = reflected_arg(node) {
+                self.reflected_args.insert(arg);
+            }
+            if let Some((arg, import_path)) = import_arg(node) {
+                self.import_args.insert(arg, import_path);
+            }
+            for child in node.children() {
+                self.walk(&child, frames);
+            }
+        }
+    }
+
+    /// Find the lambda patterns of [`reflected_args`](Index::reflected_args),
+    /// either literally or through the variable they are bound to
+    fn resolve_reflected(&mut self) {
+        let args = std::mem::take(&mut self.reflected_args);
+        let mut lambdas = args.iter().cloned().collect::<Vec<_>>();
+        for (decl_node, references) in &self.references {
+            if references
+                .iter()
+                .any(|reference| args.contains(&reference.ident))
+            {
+                if let Some(value) =
+                    AttrpathValue::cast(decl_node.clone()).and_then(|entry| entry.value())
+                {
+                    lambdas.push(strip_parens(value).syntax().clone());
+                }
+            }
+        }
+
+        for lambda in lambdas {
+            if let Some(Expr::Lambda(lambda)) = Expr::cast(lambda) {
+                if let Some(Param::Pattern(pattern)) = lambda.param() {
+                    self.reflected.insert(pattern.syntax().clone());
+                }
+            }
+        }
+    }
+
+    /// Record the bindings of the innermost scope that declares
+    /// `name` for the occurrence `ident`
+    ///
+    /// Lexical bindings take precedence over `with`, so any enclosing
+    /// `with` may only provide names that are not bound at all.
+    fn resolve(&mut self, ident: &SyntaxNode<NixLanguage>, name: &str, frames: &[Frame]) {
+        let Some(frame) = frames
+            .iter()
+            .rev()
+            .find(|frame| frame.bindings.contains_key(name))
+        else {
+            self.free.insert(name.to_string());
+            self.used_withs
+                .extend(frames.iter().filter_map(|frame| match &frame.scope {
+                    Scope::With(with) => Some(with.syntax().clone()),
+                    _ => None,
+                }));
+            return;
+        };
+
+        let body = frame.body.clone().expect("frame.body");
+        for binding in &frame.bindings[name] {
+            if self.seen.insert((ident.clone(), binding.decl_node.clone())) {
+                self.references
+                    .entry(binding.decl_node.clone())
+                    .or_default()
+                    .push(Reference {
+                        ident: ident.clone(),
+                        body: body.clone(),
+                    });
+            }
+        }
+    }
+}
+
+/// The argument of `node` if it is an application of one of the
+/// [`REFLECTING_FUNCTIONS`], like `callPackage arg` or
+/// `lib.functionArgs arg`
+fn reflected_arg(node: &SyntaxNode<NixLanguage>) -> Option<SyntaxNode<NixLanguage>> {
+    let apply = Apply::cast(node.clone())?;
+    let name = match strip_parens(apply.lambda()?) {
+        Expr::Ident(ident) => ident.syntax().text().to_string(),
+        Expr::Select(select) => {
+            let attr = select.attrpath()?.attrs().last()?;
+            Name::from_attr(&attr)?.as_str().to_string()
+        }
+        _ => return None,
+    };
+    if !REFLECTING_FUNCTIONS.contains(&name.as_str()) {
+        return None;
+    }
+    Some(strip_parens(apply.argument()?).syntax().clone())
+}
+
+/// The argument and the path literal of `node` if it is an
+/// `import ./path.nix arg`
+fn import_arg(node: &SyntaxNode<NixLanguage>) -> Option<(SyntaxNode<NixLanguage>, String)> {
+    let apply = Apply::cast(node.clone())?;
+    let Expr::Apply(import) = strip_parens(apply.lambda()?) else {
+        return None;
+    };
+    let is_import = match strip_parens(import.lambda()?) {
+        Expr::Ident(ident) => ident.syntax().text() == "import",
+        Expr::Select(select) => {
+            select.expr()?.syntax().text() == "builtins"
+                && select.attrpath()?.syntax().text() == "import"
+        }
+        _ => false,
+    };
+    let Expr::Path(path) = strip_parens(import.argument()?) else {
+        return None;
+    };
+    // no `./${...}`
+    if !is_import || path.syntax().children().next().is_some() {
+        return None;
+    }
+    let arg = strip_parens(apply.argument()?).syntax().clone();
+    Some((arg, path.syntax().text().to_string()))
+}
+
+/// Look through `(...)`
+pub(crate) fn strip_parens(mut expr: Expr) -> Expr {
+    while let Expr::Paren(paren) = &expr {
+        match paren.expr() {
+            Some(inner) => expr = inner,
+            None => break,
+        }
+    }
+    expr
+}
@@ -0,0 +1,228 @@
+//! Structured JSON of findings, see [`JSON_SCHEMA_VERSION`]
+
+use crate::{
+    binding::Binding,
+    dead_code::DeadCode,
+    edit::{dead_code_edits, dead_to_edit, standalone_edits, Edit},
+    lines::{ColumnUnit, Lines},
+    scope::Scope,
+};
+use rnix::{NixLanguage, TextRange, TextSize};
+use rowan::api::SyntaxNode;
+use serde::{Serialize, Serializer};
+
+/// Version of the JSON structure of findings
+///
+/// Incremented when fields are changed or removed, but not when
+/// fields are added.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// A [`DeadCode`] with positions
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Finding<'a> {
+    message: String,
+    rule_id: &'static str,
+    /// `unused`, `used-underscore` or `shadowing`
+    status: &'static str,
+    scope: &'a Scope,
+    binding: BindingInfo<'a>,
+    shadowed: Option<ShadowedInfo<'a>>,
+    /// Edits in the file that remove only this finding
+    edits: Vec<EditInfo>,
+    /// 1-based line of the start of the name, same as in
+    /// `binding.range.start`
+    line: usize,
+    /// 1-based byte column of the start of the name
+    column: usize,
+    /// 1-based byte column of the end of the name
+    end_column: usize,
+}
+
+impl<'a> Finding<'a> {
+    fn new(dead_code: &'a DeadCode, lines: &Lines, edits: Vec<EditInfo>) -> Self {
+        let (line, column, end_column) = lines.columns(dead_code.binding.name.text_range());
+        Finding {
+            message: dead_code.to_string(),
+            rule_id: dead_code.rule_id(),
+            status: dead_code.status(),
+            scope: &dead_code.scope,
+            binding: BindingInfo::new(&dead_code.binding, lines),
+            shadowed: dead_code
+                .shadowed
+                .as_ref()
+                .map(|(scope, binding)| ShadowedInfo {
+                    scope,
+                    binding: BindingInfo::new(binding, lines),
+                }),
+            edits,
+            line,
+            column,
+            end_column,
+        }
+    }
+}
+
+/// The findings of a file
+#[derive(Debug)]
+pub(crate) struct Findings<'a> {
+    /// Each with the edits that remove it on its own
+    pub(crate) results: Vec<Finding<'a>>,
+    /// The edits of [`edit_dead_code()`](crate::edit_dead_code) of all
+    /// `results`, which also remove a `let ... in` that they leave
+    /// empty
+    pub(crate) edits: Vec<EditInfo>,
+}
+
+/// The findings of a file with the edits that remove them
+pub(crate) fn findings<'a>(content: &str, results: &'a [DeadCode]) -> Findings<'a> {
+    let lines = Lines::new(content);
+    let edits = dead_code_edits(content, results);
+    let results = results
+        .iter()
+        .zip(standalone_edits(content, results, &edits))
+        .map(|(result, edits)| {
+            let edits = edits
+                .into_iter()
+                .map(|edit| EditInfo::new(edit, &lines))
+                .collect();
+            Finding::new(result, &lines, edits)
+        })
+        .collect();
+    Findings {
+        results,
+        edits: edits
+            .into_iter()
+            .map(|(_, edit)| EditInfo::new(edit, &lines))
+            .collect(),
+    }
+}
+
+/// An [`Edit`] with positions
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct EditInfo {
+    range: Range,
+    replacement: String,
+}
+
+impl EditInfo {
+    fn new(edit: Edit, lines: &Lines) -> Self {
+        let range = TextRange::new(
+            TextSize::try_from(edit.start).expect("TextSize"),
+            TextSize::try_from(edit.end).expect("TextSize"),
+        );
+        EditInfo {
+            range: Range::new(range, lines),
+            replacement: edit.replacement,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ShadowedInfo<'a> {
+    scope: &'a Scope,
+    binding: BindingInfo<'a>,
+}
+
+/// A [`Binding`] with positions
+#[derive(Debug, Serialize)]
+struct BindingInfo<'a> {
+    name: &'a str,
+    /// Location of the name
+    range: Range,
+    /// Location of the whole declaration
+    declaration: Range,
+}
+
+impl<'a> BindingInfo<'a> {
+    fn new(binding: &'a Binding, lines: &Lines) -> Self {
+        BindingInfo {
+            name: binding.name.as_str(),
+            range: Range::new(binding.name.text_range(), lines),
+            declaration: Range::new(binding.decl_node.text_range(), lines),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Range {
+    start: Position,
+    end: Position,
+}
+
+impl Range {
+    fn new(range: TextRange, lines: &Lines) -> Self {
+        Range {
+            start: Position::new(range.start(), lines),
+            end: Position::new(range.end(), lines),
+        }
+    }
+}
+
+/// A position with 1-based line and columns
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Position {
+    /// Byte offset in the file
+    offset: usize,
+    line: usize,
+    /// Column in bytes
+    column: usize,
+    char_column: usize,
+    utf16_column: usize,
+}
+
+impl Position {
+    fn new(offset: TextSize, lines: &Lines) -> Self {
+        let (line, column) = lines.position_in(offset, ColumnUnit::Bytes);
+        let (_, char_column) = lines.position_in(offset, ColumnUnit::Chars);
+        let (_, utf16_column) = lines.position_in(offset, ColumnUnit::Utf16);
+        Position {
+            offset: offset.into(),
+            line,
+            column,
+            char_column,
+            utf16_column,
+        }
+    }
+}
+
+/// The text of the whole file that `node` is in
+fn root_text(node: &SyntaxNode<NixLanguage>) -> String {
+    node.ancestors()
+        .last()
+        .expect("ancestors")
+        .text()
+        .to_string()
+}
+
+/// Serializes like in the `results` of JSON output, but with only the
+/// edit of the finding itself in `edits`, without the removal of a
+/// `let ... in` that it leaves empty
+impl Serialize for DeadCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let content = root_text(self.binding.name.syntax());
+        let lines = Lines::new(&content);
+        let edits = dead_to_edit(self)
+            .map(|edit| EditInfo::new(edit, &lines))
+            .into_iter()
+            .collect();
+        Finding::new(self, &lines, edits).serialize(serializer)
+    }
+}
+
+/// Serializes with `name`, and the `range` of the name and the
+/// `declaration`
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let content = root_text(self.name.syntax());
+        BindingInfo::new(self, &Lines::new(&content)).serialize(serializer)
+    }
+}
+
+/// Serializes as its [`kind()`](Scope::kind)
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.kind())
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{binding::Binding, usage};
+use crate::{
+    binding::{Binding, Name},
+    resolve::Index,
+};
 use ariadne::Color;
 use rnix::{
-    ast::{Attr, AttrSet, HasEntry, Ident, Lambda, LetIn, Param, Pattern},
+    ast::{AttrSet, HasEntry, Ident, Lambda, LegacyLet, LetIn, Param, Pattern, With},
     NixLanguage, SyntaxKind,
 };
 use rowan::{api::SyntaxNode, ast::AstNode};
@@ -16,8 +19,24 @@ pub enum Scope {
     LambdaArg(Ident, SyntaxNode<NixLanguage>),
     /// `let ... in ...`
     LetIn(LetIn),
+    /// `let { ...; body = ...; }`
+    ///
+    /// The deprecated form of `let` that evaluates to its `body`
+    /// attribute.
+    LegacyLet(LegacyLet),
     /// `rec { ... }`
     RecAttrSet(AttrSet),
+    /// `let name = { ... }; in name.attr`
+    ///
+    /// The attributes of an attrset literal that is bound in a `let`.
+    /// Not constructed by [`Scope::new()`] as it opens no scope, see
+    /// [`Settings`](`crate::Settings::warn_unused_attrs`).
+    LetAttrSet(AttrSet),
+    /// `with ...; ...`
+    ///
+    /// Declares no variables itself but provides the attributes of
+    /// its namespace to all occurrences that are not bound otherwise.
+    With(With),
 }
 
 impl fmt::Display for Scope {
@@ -26,12 +45,28 @@ impl fmt::Display for Scope {
             Scope::LambdaPattern(_, _) => write!(fmt, "lambda pattern"),
             Scope::LambdaArg(_, _) => write!(fmt, "lambda argument"),
             Scope::LetIn(_) => write!(fmt, "let binding"),
+            Scope::LegacyLet(_) => write!(fmt, "legacy let binding"),
             Scope::RecAttrSet(_) => write!(fmt, "rec attrset"),
+            Scope::LetAttrSet(_) => write!(fmt, "let attribute"),
+            Scope::With(_) => write!(fmt, "with"),
         }
     }
 }
 
 impl Scope {
+    /// Identifier of the kind of scope, like `let-in`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Scope::LambdaPattern(_, _) => "lambda-pattern",
+            Scope::LambdaArg(_, _) => "lambda-arg",
+            Scope::LetIn(_) => "let-in",
+            Scope::LegacyLet(_) => "legacy-let",
+            Scope::RecAttrSet(_) => "rec-attrset",
+            Scope::LetAttrSet(_) => "let-attrset",
+            Scope::With(_) => "with",
+        }
+    }
+
     /// Construct a new Scope *if* this is an AST node that opens a new scope
     pub fn new(node: &SyntaxNode<NixLanguage>) -> Option<Self> {
         match node.kind() {
@@ -53,6 +88,11 @@ impl Scope {
                 Some(Scope::LetIn(let_in))
             }
 
+            SyntaxKind::NODE_LEGACY_LET => {
+                let legacy_let = LegacyLet::cast(node.clone()).expect("LegacyLet::cast");
+                Some(Scope::LegacyLet(legacy_let))
+            }
+
             SyntaxKind::NODE_ATTR_SET => {
                 let attr_set = AttrSet::cast(node.clone()).expect("AttrSet::cast");
                 if attr_set.rec_token().is_some() {
@@ -62,6 +102,11 @@ impl Scope {
                 }
             }
 
+            SyntaxKind::NODE_WITH => {
+                let with = With::cast(node.clone()).expect("With::cast");
+                Some(Scope::With(with))
+            }
+
             _ => None,
         }
     }
@@ -74,16 +119,36 @@ impl Scope {
 
     /// Is this a `{ ... }: ...` which can be ignored in
     /// [`Settings`](`crate::Settings::no_lambda_pattern_names`)
-    pub fn is_lambda_pattern_name(&self, name: &Ident) -> bool {
+    pub fn is_lambda_pattern_name(&self, name: &Name) -> bool {
         if let Scope::LambdaPattern(pattern, _) = self {
-            pattern.pat_entries().any(|entry| {
-                entry.ident().expect("entry.ident").syntax().text() == name.syntax().text()
-            })
+            pattern
+                .pat_entries()
+                .any(|entry| entry.ident().expect("entry.ident").syntax().text() == name.as_str())
         } else {
             false
         }
     }
 
+    /// Is this the `{ ... }: ...` of the whole file, which can be
+    /// ignored in
+    /// [`Settings`](`crate::Settings::no_top_lambda_pattern_names`)
+    ///
+    /// Looks through any parentheses around the lambda.
+    pub fn is_top_lambda_pattern(&self) -> bool {
+        let Scope::LambdaPattern(pattern, _) = self else {
+            return false;
+        };
+        let mut node = pattern.syntax().parent();
+        while let Some(parent) = node.as_ref().and_then(SyntaxNode::parent) {
+            match parent.kind() {
+                SyntaxKind::NODE_PAREN => node = Some(parent),
+                SyntaxKind::NODE_ROOT => return true,
+                _ => return false,
+            }
+        }
+        true
+    }
+
     /// The set of [`Binding`]s this [`Scope`] introduces
     pub fn bindings(&self) -> Box<dyn Iterator<Item = Binding>> {
         match self {
@@ -110,24 +175,38 @@ impl Scope {
                 let_in
                     .inherits()
                     .flat_map(|inherit| {
-                        inherit
-                            .attrs()
-                            .filter_map(move |attr| match attr {
-                                Attr::Ident(ident) => Some(ident),
-                                _ => None,
-                            })
-                            .map(move |ident| {
-                                Binding::new(ident.clone(), ident.syntax().clone(), true)
-                            })
+                        inherit.attrs().filter_map(|attr| {
+                            Name::from_attr(&attr)
+                                .map(|name| Binding::new(name, attr.syntax().clone(), true))
+                        })
                     })
                     .chain(let_in.attrpath_values().filter_map(|entry| {
                         let attrpath = entry.attrpath().expect("entry.attrpath");
-                        match attrpath.attrs().next() {
-                            Some(Attr::Ident(name)) => {
-                                Some(Binding::new(name, entry.syntax().clone(), true))
-                            }
-                            _ => None,
-                        }
+                        attrpath
+                            .attrs()
+                            .next()
+                            .and_then(|attr| Name::from_attr(&attr))
+                            .map(|name| Binding::new(name, entry.syntax().clone(), true))
+                    })),
+            ),
+
+            Scope::LegacyLet(legacy_let) => Box::new(
+                legacy_let
+                    .inherits()
+                    .flat_map(|inherit| {
+                        inherit.attrs().filter_map(|attr| {
+                            Name::from_attr(&attr)
+                                .map(|name| Binding::new(name, attr.syntax().clone(), true))
+                        })
+                    })
+                    .chain(legacy_let.attrpath_values().filter_map(|entry| {
+                        let key = entry.attrpath().expect("entry.attrpath").attrs().next();
+                        key.and_then(|attr| Name::from_attr(&attr)).map(|name| {
+                            // `body` is the result, everything else is
+                            // only alive if it is reachable from there
+                            let mortal = name.as_str() != "body";
+                            Binding::new(name, entry.syntax().clone(), mortal)
+                        })
                     })),
             ),
 
@@ -135,23 +214,35 @@ impl Scope {
                 attr_set
                     .inherits()
                     .flat_map(|inherit| {
-                        inherit.attrs().filter_map(move |attr| match attr {
-                            Attr::Ident(ref name) => {
-                                Some(Binding::new(name.clone(), attr.syntax().clone(), false))
-                            }
-                            _ => None,
+                        inherit.attrs().filter_map(|attr| {
+                            Name::from_attr(&attr)
+                                .map(|name| Binding::new(name, attr.syntax().clone(), false))
                         })
                     })
                     .chain(attr_set.attrpath_values().filter_map(|entry| {
                         let key = entry.attrpath().expect("entry.attrpath").attrs().next();
-                        match key {
-                            Some(Attr::Ident(name)) => {
-                                Some(Binding::new(name, entry.syntax().clone(), false))
-                            }
-                            _ => None,
-                        }
+                        key.and_then(|attr| Name::from_attr(&attr))
+                            .map(|name| Binding::new(name, entry.syntax().clone(), false))
                     })),
             ),
+
+            Scope::LetAttrSet(attr_set) => Box::new(
+                attr_set
+                    .inherits()
+                    .flat_map(|inherit| {
+                        inherit.attrs().filter_map(|attr| {
+                            Name::from_attr(&attr)
+                                .map(|name| Binding::new(name, attr.syntax().clone(), true))
+                        })
+                    })
+                    .chain(attr_set.attrpath_values().filter_map(|entry| {
+                        let key = entry.attrpath().expect("entry.attrpath").attrs().next();
+                        key.and_then(|attr| Name::from_attr(&attr))
+                            .map(|name| Binding::new(name, entry.syntax().clone(), true))
+                    })),
+            ),
+
+            Scope::With(_) => Box::new(None.into_iter()),
         }
     }
 
@@ -175,6 +266,17 @@ impl Scope {
                     .chain(let_in.body().map(|body| body.syntax().clone())),
             ),
 
+            Scope::LegacyLet(legacy_let) => Box::new(
+                legacy_let
+                    .inherits()
+                    .filter_map(|inherit| inherit.from().map(|from| from.syntax().clone()))
+                    .chain(
+                        legacy_let
+                            .attrpath_values()
+                            .map(|entry| entry.syntax().clone()),
+                    ),
+            ),
+
             Scope::RecAttrSet(attr_set) => Box::new(
                 attr_set
                     .inherits()
@@ -185,32 +287,58 @@ impl Scope {
                             .map(|entry| entry.syntax().clone()),
                     ),
             ),
+
+            Scope::LetAttrSet(attr_set) => Box::new(
+                attr_set
+                    .inherits()
+                    .filter_map(|inherit| inherit.from().map(|from| from.syntax().clone()))
+                    .chain(
+                        attr_set
+                            .attrpath_values()
+                            .map(|entry| entry.syntax().clone()),
+                    ),
+            ),
+
+            Scope::With(with) => {
+                Box::new(with.body().map(|body| body.syntax().clone()).into_iter())
+            }
         }
     }
 
+    /// The subtrees of `inherit (var) ...` and `inherit vars` clauses,
+    /// and the namespace of `with`, that are evaluated in the
+    /// enclosing scope
+    pub fn inherited(&self) -> Box<dyn Iterator<Item = SyntaxNode<NixLanguage>>> {
+        let inherits = match self {
+            Scope::LambdaPattern(_, _) | Scope::LambdaArg(_, _) | Scope::LetAttrSet(_) => {
+                return Box::new(None.into_iter())
+            }
+            Scope::With(with) => {
+                return Box::new(
+                    with.namespace()
+                        .map(|namespace| namespace.syntax().clone())
+                        .into_iter(),
+                )
+            }
+            Scope::LetIn(let_in) => let_in.inherits().collect::<Vec<_>>(),
+            Scope::LegacyLet(legacy_let) => legacy_let.inherits().collect::<Vec<_>>(),
+            Scope::RecAttrSet(attr_set) => attr_set.inherits().collect::<Vec<_>>(),
+        };
+        Box::new(inherits.into_iter().map(|inherit| {
+            inherit
+                .from()
+                .map_or_else(|| inherit.syntax().clone(), |from| from.syntax().clone())
+        }))
+    }
+
     /// Check the `inherit (var) ...` and `inherit vars` clauses for a
     /// given `name`.
     ///
     /// Although a scope may shadow existing variable bindings, it can
     /// `inherit` bindings from the outer scope.
-    pub fn inherits_from(&self, name: &Ident) -> bool {
-        match self {
-            Scope::LambdaPattern(_, _) | Scope::LambdaArg(_, _) => false,
-
-            Scope::LetIn(let_in) => let_in.inherits().any(|inherit| {
-                inherit.from().map_or_else(
-                    || usage::find(name, inherit.syntax()),
-                    |from| usage::find(name, from.syntax()),
-                )
-            }),
-
-            Scope::RecAttrSet(attr_set) => attr_set.inherits().any(|inherit| {
-                inherit.from().map_or_else(
-                    || usage::find(name, inherit.syntax()),
-                    |from| usage::find(name, from.syntax()),
-                )
-            }),
-        }
+    pub fn inherits_from(&self, name: &Name) -> bool {
+        self.inherited()
+            .any(|node| Index::new(&node).is_free(name.as_str()))
     }
 
     /// Output color for dead code warnings
@@ -218,8 +346,10 @@ impl Scope {
         match self {
             Scope::LambdaPattern(_, _) => Color::Magenta,
             Scope::LambdaArg(_, _) => Color::Cyan,
-            Scope::LetIn(_) => Color::Red,
+            Scope::LetIn(_) | Scope::LegacyLet(_) => Color::Red,
             Scope::RecAttrSet(_) => Color::Yellow,
+            Scope::LetAttrSet(_) => Color::Green,
+            Scope::With(_) => Color::Blue,
         }
     }
 }
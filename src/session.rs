@@ -0,0 +1,211 @@
+use crate::{
+    dead_code::{DeadCode, Settings},
+    resolve::Index,
+};
+use rnix::{parser::ParseError, NixLanguage, SyntaxKind, TextRange};
+use rowan::{api::SyntaxNode, GreenNode, GreenNodeData};
+use std::{collections::HashMap, mem};
+
+/// Analysis of a document that is edited over time
+///
+/// Each [`edit()`](Session::edit) reparses only the innermost
+/// `{ ... }`, `[ ... ]` or `( ... )` around the change where possible.
+/// Names are then resolved and scanned again over the whole tree; only
+/// the reachability of the bindings of [`Scope`](crate::Scope)s whose
+/// syntax is unchanged is reused from the previous analysis. The
+/// [`results()`](Session::results) are the same as those of a fresh
+/// [`Settings::find_dead_code()`].
+///
+/// ```
+/// let mut session = deadnix::Session::new(
+///     deadnix::Settings::default(),
+///     "let foo = 1; in { a = foo; }",
+/// );
+/// assert_eq!(0, session.results().len());
+///
+/// session.edit(rnix::TextRange::new(22.into(), 25.into()), "2");
+/// assert_eq!(session.text(), "let foo = 1; in { a = 2; }");
+/// assert_eq!(session.results()[0].to_string(), "Unused let binding: foo");
+/// ```
+#[derive(Debug)]
+pub struct Session {
+    settings: Settings,
+    text: String,
+    root: SyntaxNode<NixLanguage>,
+    errors: Vec<ParseError>,
+    pub(crate) cache: ScopeCache,
+    results: Vec<DeadCode>,
+}
+
+impl Session {
+    /// Parse and analyze `text`
+    pub fn new(settings: Settings, text: impl Into<String>) -> Self {
+        let text = text.into();
+        let ast = rnix::Root::parse(&text);
+        let mut session = Session {
+            settings,
+            text,
+            root: ast.syntax(),
+            errors: ast.errors().to_vec(),
+            cache: ScopeCache::default(),
+            results: Vec::new(),
+        };
+        session.analyze();
+        session
+    }
+
+    /// Replace the text in `range` with `replacement`, and update the
+    /// results
+    ///
+    /// # Panics
+    ///
+    /// If `range` is out of bounds or does not lie on char boundaries.
+    pub fn edit(&mut self, range: TextRange, replacement: &str) {
+        let start = usize::from(range.start());
+        let end = usize::from(range.end());
+        let mut text = mem::take(&mut self.text);
+        text.replace_range(start..end, replacement);
+
+        if let Some(root) = self.reparse(range, replacement) {
+            self.root = root;
+        } else {
+            let ast = rnix::Root::parse(&text);
+            self.root = ast.syntax();
+            self.errors = ast.errors().to_vec();
+        }
+        self.text = text;
+        self.analyze();
+    }
+
+    /// The current text of the document
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The syntax tree of the current text
+    pub fn syntax(&self) -> &SyntaxNode<NixLanguage> {
+        &self.root
+    }
+
+    /// Syntax errors in the current text
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Dead code in the current text
+    pub fn results(&self) -> &[DeadCode] {
+        &self.results
+    }
+
+    fn analyze(&mut self) {
+        let index = Index::new(&self.root);
+        self.results =
+            self.settings
+                .find_dead_code_cached(&self.root, &index, Some(&mut self.cache));
+        self.cache.finish();
+    }
+
+    /// Reparse only the innermost delimited node that strictly
+    /// contains `range`, and splice it into the tree
+    ///
+    /// `None` if there is no such node, or if the result differs in
+    /// kind or has syntax errors, so that a full reparse is needed.
+    fn reparse(&self, range: TextRange, replacement: &str) -> Option<SyntaxNode<NixLanguage>> {
+        if !self.errors.is_empty() {
+            return None;
+        }
+
+        let node = self.root.covering_element(range).ancestors().find(|node| {
+            matches!(
+                node.kind(),
+                SyntaxKind::NODE_ATTR_SET | SyntaxKind::NODE_LIST | SyntaxKind::NODE_PAREN
+            ) && node.text_range().start() < range.start()
+                && range.end() < node.text_range().end()
+        })?;
+
+        let node_range = node.text_range();
+        let mut text = node.text().to_string();
+        text.replace_range(
+            usize::from(range.start() - node_range.start())
+                ..usize::from(range.end() - node_range.start()),
+            replacement,
+        );
+        let ast = rnix::Root::parse(&text);
+        if !ast.errors().is_empty() {
+            return None;
+        }
+        let expr = ast.tree().expr()?;
+        let expr = rowan::ast::AstNode::syntax(&expr);
+        if expr.kind() != node.kind() || usize::from(expr.text_range().len()) != text.len() {
+            return None;
+        }
+
+        Some(SyntaxNode::new_root(
+            node.replace_with(expr.green().into_owned()),
+        ))
+    }
+}
+
+/// Reachability of the bindings of each [`Scope`](crate::Scope) by
+/// the identity of its syntax
+///
+/// Unchanged subtrees keep their green nodes across incremental
+/// reparses, so that scopes which are found again need not be
+/// recomputed if their initially alive bindings are the same.
+#[derive(Debug, Default)]
+pub(crate) struct ScopeCache {
+    /// Entries of the previous analysis
+    previous: HashMap<*const GreenNodeData, CacheEntry>,
+    /// Entries of the running analysis
+    current: HashMap<*const GreenNodeData, CacheEntry>,
+    /// Number of scopes whose reachability has been computed, not
+    /// reused
+    pub(crate) computed: usize,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    /// Keeps the key alive
+    _green: GreenNode,
+    initial: Vec<bool>,
+    alive: Vec<bool>,
+}
+
+impl ScopeCache {
+    /// The alive bindings of the scope of `node`, computed with
+    /// `reachable` unless cached
+    pub(crate) fn alive(
+        &mut self,
+        node: &SyntaxNode<NixLanguage>,
+        initial: Vec<bool>,
+        reachable: impl FnOnce(Vec<bool>) -> Vec<bool>,
+    ) -> Vec<bool> {
+        let green = node.green().into_owned();
+        let key = std::ptr::from_ref::<GreenNodeData>(&green);
+        let cached = self
+            .current
+            .get(&key)
+            .or_else(|| self.previous.get(&key))
+            .filter(|entry| entry.initial == initial)
+            .map(|entry| entry.alive.clone());
+        let alive = cached.unwrap_or_else(|| {
+            self.computed += 1;
+            reachable(initial.clone())
+        });
+
+        self.current.insert(
+            key,
+            CacheEntry {
+                _green: green,
+                initial,
+                alive: alive.clone(),
+            },
+        );
+        alive
+    }
+
+    /// Drop the entries of scopes that no longer exist
+    fn finish(&mut self) {
+        self.previous = mem::take(&mut self.current);
+    }
+}
@@ -0,0 +1,161 @@
+#![cfg(test)]
+
+use crate::{dead_code::Settings, session::Session};
+use rnix::{TextRange, TextSize};
+
+fn summary(results: &[crate::DeadCode]) -> Vec<(String, TextRange)> {
+    results
+        .iter()
+        .map(|result| (result.to_string(), result.binding.name.text_range()))
+        .collect()
+}
+
+/// Apply `edits` one by one, comparing against a fresh analysis
+fn run_edits(settings: &Settings, content: &str, edits: &[(&str, &str)]) -> Session {
+    let mut session = Session::new(settings.clone(), content);
+    for (old, new) in edits {
+        let start = session.text().find(old).expect("edit not found");
+        let range = TextRange::at(TextSize::from(start as u32), TextSize::of(*old));
+        session.edit(range, new);
+
+        let ast = rnix::Root::parse(session.text());
+        assert_eq!(session.syntax().to_string(), session.text());
+        assert_eq!(*session.syntax().green(), *ast.syntax().green());
+        assert_eq!(session.errors().len(), ast.errors().len());
+        assert_eq!(
+            summary(session.results()),
+            summary(&settings.find_dead_code(&ast.syntax()))
+        );
+    }
+    session
+}
+
+#[test]
+fn session_new() {
+    let session = Session::new(Settings::default(), "let dead = 1; in 2");
+    assert_eq!(1, session.results().len());
+    assert_eq!(session.results()[0].binding.name.to_string(), "dead");
+}
+
+#[test]
+fn session_edit_attrset() {
+    let session = run_edits(
+        &Settings::default(),
+        "let foo = 1; in { a = foo; }",
+        &[("foo; }", "2; }")],
+    );
+    assert_eq!(1, session.results().len());
+    assert_eq!(session.results()[0].binding.name.to_string(), "foo");
+}
+
+#[test]
+fn session_edit_revive() {
+    let session = run_edits(
+        &Settings::default(),
+        "let foo = 1; in { a = 2; }",
+        &[("2", "foo")],
+    );
+    assert_eq!(0, session.results().len());
+}
+
+#[test]
+fn session_edit_full_reparse() {
+    let session = run_edits(
+        &Settings::default(),
+        "let foo = 1; in foo",
+        &[("in foo", "in 2"), ("let ", "let bar = 2; ")],
+    );
+    assert_eq!(2, session.results().len());
+}
+
+#[test]
+fn session_edit_syntax_error() {
+    let session = run_edits(
+        &Settings::default(),
+        "let foo = 1; in { a = foo; }",
+        &[("a = foo;", "a = ;"), ("a = ;", "a = foo;")],
+    );
+    assert_eq!(0, session.errors().len());
+    assert_eq!(0, session.results().len());
+}
+
+#[test]
+fn session_edit_delimiter() {
+    let session = run_edits(
+        &Settings::default(),
+        "let foo = 1; in [ ({ a = foo; }) ]",
+        &[("})", "} )"), ("[ ", "[ 1 "), ("({", "( {")],
+    );
+    assert_eq!(0, session.results().len());
+}
+
+#[test]
+fn session_edit_rec_binding() {
+    // a new binding changes how the unchanged siblings resolve
+    let session = run_edits(
+        &Settings {
+            warn_unused_rec: true,
+            ..Settings::default()
+        },
+        "x: rec { a = { b = x; }; c = 1; }",
+        &[("c = 1;", "x = 1;")],
+    );
+    assert_eq!(1, session.results().len());
+    assert_eq!(
+        session.results()[0].to_string(),
+        "Unused lambda argument: x"
+    );
+}
+
+#[test]
+fn session_edit_with() {
+    let session = run_edits(
+        &Settings {
+            warn_unused_with: true,
+            ..Settings::default()
+        },
+        "let a = 1; in with lib; { b = [ (x: a) ]; c = { }; }",
+        &[
+            ("{ }", "{ d = foo; }"),
+            ("{ d = foo; }", "{ d = a; }"),
+            ("x: a", "x: x"),
+        ],
+    );
+    assert_eq!(1, session.results().len());
+    assert_eq!(session.results()[0].to_string(), "Unused with: lib");
+}
+
+#[test]
+fn session_edit_pragma() {
+    let session = run_edits(
+        &Settings::default(),
+        "{ x = [\n  (dead: 1)\n]; }",
+        &[
+            ("[\n", "[\n  # deadnix: skip\n"),
+            ("# deadnix: skip", "# comment"),
+        ],
+    );
+    assert_eq!(1, session.results().len());
+}
+
+#[test]
+fn session_edit_reuses_unchanged_scopes() {
+    let content = (0..50)
+        .map(|i| format!("  f{i} = {{ a, b }}: let x = a; in [ x ];\n"))
+        .collect::<Vec<_>>()
+        .concat();
+    let content = format!("{{\n{content}  g = [ (dead: 1) ];\n}}");
+    let mut session = run_edits(&Settings::default(), &content, &[]);
+    assert_eq!(51, session.results().len());
+
+    session.cache.computed = 0;
+    let start = session.text().find("(dead: 1)").unwrap();
+    session.edit(
+        TextRange::at(TextSize::from(start as u32 + 7), 1.into()),
+        "dead",
+    );
+    assert_eq!(50, session.results().len());
+    // names are resolved again everywhere, but only the reachability
+    // of the lambda of `g` is new
+    assert_eq!(1, session.cache.computed);
+}